@@ -0,0 +1,500 @@
+//! Flag lookup by country name, endonym, and ISO 3166 code, plus a builder that
+//! turns two typed letters into their regional-indicator pair.
+
+use std::sync::LazyLock;
+
+use emoji::Emoji;
+
+use crate::lookup::by_glyph;
+
+/// A flag entry: ISO 3166-1 alpha-2 code, alpha-3 code (empty when there is
+/// none), and the names it answers to (English first, then endonyms).
+struct Country {
+	alpha2: &'static str,
+	alpha3: &'static str,
+	names:  &'static [&'static str],
+}
+
+const fn c(alpha2: &'static str, alpha3: &'static str, names: &'static [&'static str]) -> Country {
+	Country { alpha2, alpha3, names }
+}
+
+/// Subdivision flags are tag sequences rather than regional-indicator pairs,
+/// keyed by their ISO 3166-2 code with the hyphen removed.
+struct Subdivision {
+	code:  &'static str,
+	short: &'static str,
+	names: &'static [&'static str],
+}
+
+const SUBDIVISIONS: &[Subdivision] = &[
+	Subdivision { code: "gbeng", short: "eng", names: &["england"] },
+	Subdivision { code: "gbsct", short: "sct", names: &["scotland", "alba"] },
+	Subdivision { code: "gbwls", short: "wls", names: &["wales", "cymru"] },
+];
+
+#[rustfmt::skip]
+const COUNTRIES: &[Country] = &[
+	c("ac", "",    &["ascension island"]),
+	c("ad", "and", &["andorra"]),
+	c("ae", "are", &["united arab emirates", "uae", "al imarat"]),
+	c("af", "afg", &["afghanistan"]),
+	c("ag", "atg", &["antigua and barbuda", "antigua", "barbuda"]),
+	c("ai", "aia", &["anguilla"]),
+	c("al", "alb", &["albania", "shqiperia"]),
+	c("am", "arm", &["armenia", "hayastan"]),
+	c("ao", "ago", &["angola"]),
+	c("aq", "ata", &["antarctica"]),
+	c("ar", "arg", &["argentina"]),
+	c("as", "asm", &["american samoa"]),
+	c("at", "aut", &["austria", "österreich"]),
+	c("au", "aus", &["australia"]),
+	c("aw", "abw", &["aruba"]),
+	c("ax", "ala", &["åland islands", "aland"]),
+	c("az", "aze", &["azerbaijan", "azərbaycan"]),
+	c("ba", "bih", &["bosnia and herzegovina", "bosnia", "bosna i hercegovina"]),
+	c("bb", "brb", &["barbados"]),
+	c("bd", "bgd", &["bangladesh"]),
+	c("be", "bel", &["belgium", "belgië", "belgique", "belgien"]),
+	c("bf", "bfa", &["burkina faso"]),
+	c("bg", "bgr", &["bulgaria", "българия", "balgariya"]),
+	c("bh", "bhr", &["bahrain"]),
+	c("bi", "bdi", &["burundi"]),
+	c("bj", "ben", &["benin", "bénin"]),
+	c("bl", "blm", &["st. barthélemy", "saint barthélemy"]),
+	c("bm", "bmu", &["bermuda"]),
+	c("bn", "brn", &["brunei"]),
+	c("bo", "bol", &["bolivia"]),
+	c("bq", "bes", &["caribbean netherlands", "bonaire"]),
+	c("br", "bra", &["brazil", "brasil"]),
+	c("bs", "bhs", &["bahamas"]),
+	c("bt", "btn", &["bhutan"]),
+	c("bv", "bvt", &["bouvet island"]),
+	c("bw", "bwa", &["botswana"]),
+	c("by", "blr", &["belarus", "беларусь", "belarus'"]),
+	c("bz", "blz", &["belize"]),
+	c("ca", "can", &["canada"]),
+	c("cc", "cck", &["cocos (keeling) islands", "cocos islands"]),
+	c("cd", "cod", &["congo - kinshasa", "democratic republic of the congo", "drc"]),
+	c("cf", "caf", &["central african republic", "centrafrique"]),
+	c("cg", "cog", &["congo - brazzaville", "republic of the congo"]),
+	c("ch", "che", &["switzerland", "schweiz", "suisse", "svizzera", "svizra"]),
+	c("ci", "civ", &["côte d’ivoire", "côte d'ivoire", "ivory coast"]),
+	c("ck", "cok", &["cook islands"]),
+	c("cl", "chl", &["chile"]),
+	c("cm", "cmr", &["cameroon", "cameroun"]),
+	c("cn", "chn", &["china", "中国", "zhongguo"]),
+	c("co", "col", &["colombia"]),
+	c("cp", "",    &["clipperton island"]),
+	c("cr", "cri", &["costa rica"]),
+	c("cu", "cub", &["cuba"]),
+	c("cv", "cpv", &["cape verde", "cabo verde"]),
+	c("cw", "cuw", &["curaçao"]),
+	c("cx", "cxr", &["christmas island"]),
+	c("cy", "cyp", &["cyprus", "κύπρος", "kıbrıs"]),
+	c("cz", "cze", &["czechia", "czech republic", "česko"]),
+	c("de", "deu", &["germany", "deutschland"]),
+	c("dg", "",    &["diego garcia"]),
+	c("dj", "dji", &["djibouti"]),
+	c("dk", "dnk", &["denmark", "danmark"]),
+	c("dm", "dma", &["dominica"]),
+	c("do", "dom", &["dominican republic", "república dominicana"]),
+	c("dz", "dza", &["algeria", "algérie", "al jazair"]),
+	c("ea", "",    &["ceuta and melilla", "ceuta", "melilla"]),
+	c("ec", "ecu", &["ecuador"]),
+	c("ee", "est", &["estonia", "eesti"]),
+	c("eg", "egy", &["egypt", "misr"]),
+	c("eh", "esh", &["western sahara"]),
+	c("er", "eri", &["eritrea"]),
+	c("es", "esp", &["spain", "españa"]),
+	c("et", "eth", &["ethiopia", "ityop'iya"]),
+	c("eu", "",    &["european union"]),
+	c("fi", "fin", &["finland", "suomi"]),
+	c("fj", "fji", &["fiji"]),
+	c("fk", "flk", &["falkland islands", "falklands"]),
+	c("fm", "fsm", &["micronesia"]),
+	c("fo", "fro", &["faroe islands", "føroyar"]),
+	c("fr", "fra", &["france"]),
+	c("ga", "gab", &["gabon"]),
+	c("gb", "gbr", &["united kingdom", "uk", "great britain", "britain"]),
+	c("gd", "grd", &["grenada"]),
+	c("ge", "geo", &["georgia", "საქართველო", "sakartvelo"]),
+	c("gf", "guf", &["french guiana", "guyane"]),
+	c("gg", "ggy", &["guernsey"]),
+	c("gh", "gha", &["ghana"]),
+	c("gi", "gib", &["gibraltar"]),
+	c("gl", "grl", &["greenland", "kalaallit nunaat", "grønland"]),
+	c("gm", "gmb", &["gambia"]),
+	c("gn", "gin", &["guinea", "guinée"]),
+	c("gp", "glp", &["guadeloupe"]),
+	c("gq", "gnq", &["equatorial guinea", "guinea ecuatorial"]),
+	c("gr", "grc", &["greece", "ελλάδα", "hellas", "ellada"]),
+	c("gs", "sgs", &["south georgia and south sandwich islands", "south georgia"]),
+	c("gt", "gtm", &["guatemala"]),
+	c("gu", "gum", &["guam"]),
+	c("gw", "gnb", &["guinea-bissau", "guiné-bissau"]),
+	c("gy", "guy", &["guyana"]),
+	c("hk", "hkg", &["hong kong", "香港"]),
+	c("hm", "hmd", &["heard and mcdonald islands"]),
+	c("hn", "hnd", &["honduras"]),
+	c("hr", "hrv", &["croatia", "hrvatska"]),
+	c("ht", "hti", &["haiti", "haïti"]),
+	c("hu", "hun", &["hungary", "magyarország"]),
+	c("ic", "",    &["canary islands", "islas canarias"]),
+	c("id", "idn", &["indonesia"]),
+	c("ie", "irl", &["ireland", "éire"]),
+	c("il", "isr", &["israel", "ישראל", "yisrael"]),
+	c("im", "imn", &["isle of man"]),
+	c("in", "ind", &["india", "bharat", "भारत"]),
+	c("io", "iot", &["british indian ocean territory"]),
+	c("iq", "irq", &["iraq", "al iraq"]),
+	c("ir", "irn", &["iran", "ایران"]),
+	c("is", "isl", &["iceland", "ísland"]),
+	c("it", "ita", &["italy", "italia"]),
+	c("je", "jey", &["jersey"]),
+	c("jm", "jam", &["jamaica"]),
+	c("jo", "jor", &["jordan", "al urdun"]),
+	c("jp", "jpn", &["japan", "日本", "nippon", "nihon"]),
+	c("ke", "ken", &["kenya"]),
+	c("kg", "kgz", &["kyrgyzstan", "кыргызстан"]),
+	c("kh", "khm", &["cambodia", "kampuchea"]),
+	c("ki", "kir", &["kiribati"]),
+	c("km", "com", &["comoros", "comores"]),
+	c("kn", "kna", &["st. kitts and nevis", "saint kitts and nevis"]),
+	c("kp", "prk", &["north korea", "조선", "chosŏn"]),
+	c("kr", "kor", &["south korea", "korea", "한국", "hanguk"]),
+	c("kw", "kwt", &["kuwait"]),
+	c("ky", "cym", &["cayman islands"]),
+	c("kz", "kaz", &["kazakhstan", "қазақстан", "казахстан"]),
+	c("la", "lao", &["laos"]),
+	c("lb", "lbn", &["lebanon", "lubnan", "liban"]),
+	c("lc", "lca", &["st. lucia", "saint lucia"]),
+	c("li", "lie", &["liechtenstein"]),
+	c("lk", "lka", &["sri lanka"]),
+	c("lr", "lbr", &["liberia"]),
+	c("ls", "lso", &["lesotho"]),
+	c("lt", "ltu", &["lithuania", "lietuva"]),
+	c("lu", "lux", &["luxembourg", "lëtzebuerg", "luxemburg"]),
+	c("lv", "lva", &["latvia", "latvija"]),
+	c("ly", "lby", &["libya", "libiya"]),
+	c("ma", "mar", &["morocco", "maroc", "al maghrib"]),
+	c("mc", "mco", &["monaco"]),
+	c("md", "mda", &["moldova"]),
+	c("me", "mne", &["montenegro", "crna gora"]),
+	c("mf", "maf", &["st. martin", "saint martin"]),
+	c("mg", "mdg", &["madagascar", "madagasikara"]),
+	c("mh", "mhl", &["marshall islands"]),
+	c("mk", "mkd", &["north macedonia", "macedonia", "северна македонија"]),
+	c("ml", "mli", &["mali"]),
+	c("mm", "mmr", &["myanmar", "burma"]),
+	c("mn", "mng", &["mongolia", "монгол улс"]),
+	c("mo", "mac", &["macao", "macau", "澳門"]),
+	c("mp", "mnp", &["northern mariana islands"]),
+	c("mq", "mtq", &["martinique"]),
+	c("mr", "mrt", &["mauritania", "mauritanie"]),
+	c("ms", "msr", &["montserrat"]),
+	c("mt", "mlt", &["malta"]),
+	c("mu", "mus", &["mauritius", "maurice"]),
+	c("mv", "mdv", &["maldives"]),
+	c("mw", "mwi", &["malawi"]),
+	c("mx", "mex", &["mexico", "méxico"]),
+	c("my", "mys", &["malaysia"]),
+	c("mz", "moz", &["mozambique", "moçambique"]),
+	c("na", "nam", &["namibia"]),
+	c("nc", "ncl", &["new caledonia", "nouvelle-calédonie"]),
+	c("ne", "ner", &["niger"]),
+	c("nf", "nfk", &["norfolk island"]),
+	c("ng", "nga", &["nigeria"]),
+	c("ni", "nic", &["nicaragua"]),
+	c("nl", "nld", &["netherlands", "nederland", "holland"]),
+	c("no", "nor", &["norway", "norge", "noreg"]),
+	c("np", "npl", &["nepal", "नेपाल"]),
+	c("nr", "nru", &["nauru"]),
+	c("nu", "niu", &["niue"]),
+	c("nz", "nzl", &["new zealand", "aotearoa"]),
+	c("om", "omn", &["oman"]),
+	c("pa", "pan", &["panama", "panamá"]),
+	c("pe", "per", &["peru", "perú"]),
+	c("pf", "pyf", &["french polynesia", "polynésie française"]),
+	c("pg", "png", &["papua new guinea"]),
+	c("ph", "phl", &["philippines", "pilipinas"]),
+	c("pk", "pak", &["pakistan"]),
+	c("pl", "pol", &["poland", "polska"]),
+	c("pm", "spm", &["st. pierre and miquelon", "saint pierre and miquelon"]),
+	c("pn", "pcn", &["pitcairn islands"]),
+	c("pr", "pri", &["puerto rico"]),
+	c("ps", "pse", &["palestinian territories", "palestine", "filastin"]),
+	c("pt", "prt", &["portugal"]),
+	c("pw", "plw", &["palau"]),
+	c("py", "pry", &["paraguay"]),
+	c("qa", "qat", &["qatar"]),
+	c("re", "reu", &["réunion"]),
+	c("ro", "rou", &["romania", "românia"]),
+	c("rs", "srb", &["serbia", "србија", "srbija"]),
+	c("ru", "rus", &["russia", "россия", "rossiya"]),
+	c("rw", "rwa", &["rwanda"]),
+	c("sa", "sau", &["saudi arabia", "as saudiyah"]),
+	c("sb", "slb", &["solomon islands"]),
+	c("sc", "syc", &["seychelles"]),
+	c("sd", "sdn", &["sudan"]),
+	c("se", "swe", &["sweden", "sverige"]),
+	c("sg", "sgp", &["singapore", "singapura", "新加坡"]),
+	c("sh", "shn", &["st. helena", "saint helena"]),
+	c("si", "svn", &["slovenia", "slovenija"]),
+	c("sj", "sjm", &["svalbard and jan mayen", "svalbard"]),
+	c("sk", "svk", &["slovakia", "slovensko"]),
+	c("sl", "sle", &["sierra leone"]),
+	c("sm", "smr", &["san marino"]),
+	c("sn", "sen", &["senegal", "sénégal"]),
+	c("so", "som", &["somalia", "soomaaliya"]),
+	c("sr", "sur", &["suriname"]),
+	c("ss", "ssd", &["south sudan"]),
+	c("st", "stp", &["são tomé and príncipe", "sao tome"]),
+	c("sv", "slv", &["el salvador"]),
+	c("sx", "sxm", &["sint maarten"]),
+	c("sy", "syr", &["syria", "suriyah"]),
+	c("sz", "swz", &["eswatini", "swaziland"]),
+	c("ta", "",    &["tristan da cunha"]),
+	c("tc", "tca", &["turks and caicos islands"]),
+	c("td", "tcd", &["chad", "tchad"]),
+	c("tf", "atf", &["french southern territories"]),
+	c("tg", "tgo", &["togo"]),
+	c("th", "tha", &["thailand", "ประเทศไทย", "prathet thai"]),
+	c("tj", "tjk", &["tajikistan", "тоҷикистон"]),
+	c("tk", "tkl", &["tokelau"]),
+	c("tl", "tls", &["timor-leste", "east timor"]),
+	c("tm", "tkm", &["turkmenistan", "türkmenistan"]),
+	c("tn", "tun", &["tunisia", "tunisie", "tunis"]),
+	c("to", "ton", &["tonga"]),
+	c("tr", "tur", &["türkiye", "turkey", "turkiye"]),
+	c("tt", "tto", &["trinidad and tobago", "trinidad", "tobago"]),
+	c("tv", "tuv", &["tuvalu"]),
+	c("tw", "twn", &["taiwan", "臺灣", "台灣"]),
+	c("tz", "tza", &["tanzania"]),
+	c("ua", "ukr", &["ukraine", "україна", "ukraina"]),
+	c("ug", "uga", &["uganda"]),
+	c("um", "umi", &["u.s. outlying islands", "us outlying islands"]),
+	c("un", "",    &["united nations"]),
+	c("us", "usa", &["united states", "usa", "america", "united states of america"]),
+	c("uy", "ury", &["uruguay"]),
+	c("uz", "uzb", &["uzbekistan", "o'zbekiston"]),
+	c("va", "vat", &["vatican city", "vatican", "holy see", "città del vaticano"]),
+	c("vc", "vct", &["st. vincent and grenadines", "saint vincent and the grenadines"]),
+	c("ve", "ven", &["venezuela"]),
+	c("vg", "vgb", &["british virgin islands"]),
+	c("vi", "vir", &["u.s. virgin islands", "us virgin islands"]),
+	c("vn", "vnm", &["vietnam", "việt nam"]),
+	c("vu", "vut", &["vanuatu"]),
+	c("wf", "wlf", &["wallis and futuna"]),
+	c("ws", "wsm", &["samoa"]),
+	c("xk", "xkx", &["kosovo", "kosova", "косово"]),
+	c("ye", "yem", &["yemen", "al yaman"]),
+	c("yt", "myt", &["mayotte"]),
+	c("za", "zaf", &["south africa", "suid-afrika", "mzansi"]),
+	c("zm", "zmb", &["zambia"]),
+	c("zw", "zwe", &["zimbabwe"]),
+];
+
+/// Lowercases and strips the Latin diacritics people tend to leave out when
+/// typing endonyms, so `osterreich` still finds `Österreich`.
+fn fold(text: &str) -> String {
+	text
+		.trim()
+		.to_lowercase()
+		.chars()
+		.map(|ch| match ch {
+			'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => 'a',
+			'ç' => 'c',
+			'è' | 'é' | 'ê' | 'ë' | 'ə' => 'e',
+			'ì' | 'í' | 'î' | 'ï' | 'ı' => 'i',
+			'ñ' => 'n',
+			'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ŏ' => 'o',
+			'ù' | 'ú' | 'û' | 'ü' => 'u',
+			'ý' | 'ÿ' => 'y',
+			'č' => 'c',
+			'š' => 's',
+			'ž' => 'z',
+			'ệ' => 'e',
+			'’' => '\'',
+			other => other,
+		})
+		.collect()
+}
+
+/// Builds the regional-indicator pair for two ASCII letters, e.g. `de` → 🇩🇪.
+pub(crate) fn regional_indicator_pair(letters: &str) -> Option<String> {
+	let mut chars = letters.chars();
+	let (Some(first), Some(second), None) = (chars.next(), chars.next(), chars.next()) else {
+		return None;
+	};
+
+	[first, second]
+		.into_iter()
+		.map(|ch| {
+			ch.is_ascii_alphabetic()
+				.then(|| char::from_u32(0x1f1e6 + (ch.to_ascii_lowercase() as u32 - 'a' as u32)))
+				.flatten()
+		})
+		.collect()
+}
+
+/// Builds the tag sequence for a subdivision flag, e.g. `gbsct` → 🏴󠁧󠁢󠁳󠁣󠁴󠁿.
+fn subdivision_tag_sequence(code: &str) -> String {
+	std::iter::once('\u{1f3f4}')
+		.chain(code.chars().filter_map(|ch| char::from_u32(0xe0000 + ch as u32)))
+		.chain(std::iter::once('\u{e007f}'))
+		.collect()
+}
+
+/// A flag and what finds it: codes that must be typed in full, and folded
+/// names that also match by prefix.
+struct Flag {
+	emoji: &'static Emoji,
+	codes: Vec<&'static str>,
+	names: Vec<String>,
+}
+
+/// Every known flag with its names folded, built once rather than per
+/// keystroke.
+static FLAGS: LazyLock<Vec<Flag>> = LazyLock::new(|| {
+	let countries = COUNTRIES.iter().filter_map(|country| {
+		let emoji = regional_indicator_pair(country.alpha2).and_then(|pair| by_glyph(&pair))?;
+		let codes = [country.alpha3].into_iter().filter(|code| !code.is_empty()).collect();
+		Some(Flag { emoji, codes, names: country.names.iter().map(|name| fold(name)).collect() })
+	});
+	let subdivisions = SUBDIVISIONS.iter().filter_map(|subdivision| {
+		Some(Flag {
+			emoji: by_glyph(&subdivision_tag_sequence(subdivision.code))?,
+			codes: vec![subdivision.code, subdivision.short],
+			names: subdivision.names.iter().map(|name| fold(name)).collect(),
+		})
+	});
+	countries.chain(subdivisions).collect()
+});
+
+/// Whether `emoji` is a country or subdivision flag.
+fn is_flag(emoji: &Emoji) -> bool { FLAGS.iter().any(|flag| flag.emoji.glyph == emoji.glyph) }
+
+/// Returns the flags matching `query`, exact code and name hits first and then
+/// names that start with it.
+pub(crate) fn search_flags(query: &str) -> Vec<&'static Emoji> {
+	let query = fold(query);
+	if query.is_empty() {
+		return Vec::new();
+	}
+	let compact = query.replace('-', "");
+
+	let mut exact = Vec::new();
+	let mut prefixed = Vec::new();
+
+	if let Some(pair) = regional_indicator_pair(&query) {
+		exact.extend(by_glyph(&pair));
+	}
+
+	for flag in FLAGS.iter() {
+		if flag.codes.iter().any(|code| *code == query || *code == compact)
+			|| flag.names.contains(&query)
+		{
+			exact.push(flag.emoji);
+		} else if query.chars().count() >= 3 && flag.names.iter().any(|name| name.starts_with(&query)) {
+			prefixed.push(flag.emoji);
+		}
+	}
+
+	let mut results: Vec<&'static Emoji> = Vec::new();
+	for emoji in exact.into_iter().chain(prefixed) {
+		if !results.iter().any(|seen| seen.glyph == emoji.glyph) {
+			results.push(emoji);
+		}
+	}
+	results
+}
+
+/// Merges flag hits into the searcher's results. Codes and name prefixes are
+/// also ordinary words ("in", "can", "ice"), so flags only lead when the
+/// searcher found nothing but flags; otherwise they follow its results.
+pub(crate) fn merge_flags(
+	found: impl IntoIterator<Item = &'static Emoji>,
+	flags: Vec<&'static Emoji>,
+) -> Vec<&'static Emoji> {
+	let found: Vec<&'static Emoji> = found.into_iter().collect();
+	let (first, second) =
+		if found.iter().any(|emoji| !is_flag(emoji)) { (found, flags) } else { (flags, found) };
+
+	let mut results: Vec<&'static Emoji> = Vec::with_capacity(first.len() + second.len());
+	for emoji in first.into_iter().chain(second) {
+		if !results.iter().any(|seen| seen.glyph == emoji.glyph) {
+			results.push(emoji);
+		}
+	}
+	results
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const GERMANY: &str = "\u{1F1E9}\u{1F1EA}";
+	const AUSTRIA: &str = "\u{1F1E6}\u{1F1F9}";
+	const ICELAND: &str = "\u{1F1EE}\u{1F1F8}";
+	const SCOTLAND: &str = "\u{1F3F4}\u{E0067}\u{E0062}\u{E0073}\u{E0063}\u{E0074}\u{E007F}";
+	const ICE: &str = "\u{1F9CA}";
+
+	fn emoji(glyph: &str) -> &'static Emoji {
+		by_glyph(glyph).unwrap_or_else(|| panic!("{glyph} is missing from the emoji data"))
+	}
+
+	fn glyphs(found: Vec<&'static Emoji>) -> Vec<&'static str> {
+		found.iter().map(|emoji| emoji.glyph).collect()
+	}
+
+	#[test]
+	fn builds_regional_indicator_pairs() {
+		assert_eq!(regional_indicator_pair("de").as_deref(), Some(GERMANY));
+		assert_eq!(regional_indicator_pair("DE").as_deref(), Some(GERMANY));
+		assert_eq!(regional_indicator_pair("d1"), None);
+		assert_eq!(regional_indicator_pair("deu"), None);
+	}
+
+	#[test]
+	fn builds_subdivision_tag_sequences() {
+		assert_eq!(subdivision_tag_sequence("gbsct"), SCOTLAND);
+	}
+
+	#[test]
+	fn folds_case_and_accents() {
+		assert_eq!(fold(" Österreich "), "osterreich");
+		assert_eq!(fold("Ísland"), "island");
+		assert_eq!(fold("Việt Nam"), "viet nam");
+	}
+
+	#[test]
+	fn finds_flags_by_code() {
+		assert_eq!(glyphs(search_flags("de")), [GERMANY]);
+		assert_eq!(glyphs(search_flags("DEU")), [GERMANY]);
+		assert_eq!(glyphs(search_flags("gb-sct")), [SCOTLAND]);
+		assert_eq!(glyphs(search_flags("sct")), [SCOTLAND]);
+	}
+
+	#[test]
+	fn finds_flags_by_folded_name() {
+		assert_eq!(glyphs(search_flags("Österreich")), [AUSTRIA]);
+		assert_eq!(glyphs(search_flags("osterreich")), [AUSTRIA]);
+		assert_eq!(glyphs(search_flags("alba")), [SCOTLAND]);
+		assert!(glyphs(search_flags("ice")).contains(&ICELAND));
+	}
+
+	#[test]
+	fn flags_follow_other_matches() {
+		assert_eq!(glyphs(merge_flags([emoji(ICE)], vec![emoji(ICELAND)])), [ICE, ICELAND]);
+		assert_eq!(glyphs(merge_flags([emoji(ICE), emoji(ICELAND)], vec![emoji(ICELAND)])), [
+			ICE, ICELAND
+		]);
+	}
+
+	#[test]
+	fn flags_lead_when_nothing_else_matches() {
+		assert_eq!(glyphs(merge_flags([], vec![emoji(GERMANY)])), [GERMANY]);
+		assert_eq!(glyphs(merge_flags([emoji(AUSTRIA)], vec![emoji(GERMANY)])), [GERMANY, AUSTRIA]);
+	}
+}
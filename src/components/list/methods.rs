@@ -20,7 +20,8 @@ fn grouped_emojis() -> Vec<GroupedEmojis> {
 
 fn search_emojis(text: &str) -> Vec<&'static Emoji> {
 	let matcher: &'static emoji_search::EmojiSearcher = &*super::SEARCHER;
-	matcher.search_best_matching_emojis(text, Some(100)).unwrap()
+	let found = matcher.search_best_matching_emojis(text, Some(100)).unwrap();
+	super::flags::merge_flags(found, super::flags::search_flags(text))
 }
 
 impl EmojiListDelegate {
@@ -2,6 +2,7 @@
 
 use std::sync::LazyLock;
 
+pub(crate) mod flags;
 pub(crate) mod methods;
 pub(crate) mod row;
 pub(crate) mod types;
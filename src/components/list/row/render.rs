@@ -3,18 +3,22 @@
 
 use gpui::{
 	App, BorrowAppContext, BoxShadow, Edges, Hsla, InteractiveElement, IntoElement, MouseButton,
-	ParentElement, RenderOnce, StatefulInteractiveElement, StyleRefinement, Styled, Window, div,
-	hsla, px,
+	ParentElement, RenderOnce, SharedString, StatefulInteractiveElement, StyleRefinement, Styled,
+	Window, div, hsla, px,
 };
 use gpui_component::{StyledExt, h_flex, popover::Popover, tooltip::Tooltip};
 
 use super::types::{EmojiRow, EmojiWrapper};
 use crate::{
 	components::{
-		types::{PopoverState, ToneIndex},
+		types::{PopoverState, PreviewPresentation, ToneIndex},
 		variants,
 	},
-	insert::close_and_insert,
+	config::CONFIG,
+	insert::{
+		close_and_insert,
+		format::{Presentation, is_ambiguous, other_presentation},
+	},
 };
 
 impl RenderOnce for EmojiRow {
@@ -24,6 +28,7 @@ impl RenderOnce for EmojiRow {
 		let font_size = self.font_size;
 		let selected_row = self.contains_selection;
 		let selected_col = self.selected_column;
+		let preview = cx.global::<PreviewPresentation>().0;
 
 		h_flex()
 			.key_context("ListBody")
@@ -34,10 +39,35 @@ impl RenderOnce for EmojiRow {
 				let tone_index = cx.global::<ToneIndex>();
 				let is_selected = selected_row && selected_col == Some(idx);
 
-				let pure_emoji = if let Some(tones) = emoji.skin_tones {
-					tones.get(tone_index.0 as usize).unwrap_or(emoji).glyph
+				let toned_emoji = if let Some(tones) = emoji.skin_tones {
+					tones.get(tone_index.0 as usize).unwrap_or(emoji)
+				} else {
+					emoji
+				};
+				let pure_emoji = toned_emoji.glyph;
+				let shown: SharedString = if preview {
+					CONFIG.presentation.apply(pure_emoji).into_owned().into()
+				} else {
+					pure_emoji.into()
+				};
+
+				// Emoji with a text presentation show the other form in a corner of
+				// their cell, so it is seen without hovering.
+				let other_form = is_ambiguous(pure_emoji).then(|| {
+					div()
+						.absolute()
+						.bottom_0()
+						.right_0()
+						.text_size(font_size * 0.4)
+						.child(SharedString::from(other_presentation(&shown).into_owned()))
+				});
+
+				let label: SharedString = if is_ambiguous(pure_emoji) {
+					let text = Presentation::Text.apply(pure_emoji);
+					let colour = Presentation::Emoji.apply(pure_emoji);
+					format!("{}  {text} {colour}", emoji.name).into()
 				} else {
-					emoji.glyph
+					emoji.name.into()
 				};
 
 				let mut base_element = div()
@@ -78,10 +108,12 @@ impl RenderOnce for EmojiRow {
 							spread_radius: gpui::px(7.),
 						}])
 					})
-					.tooltip(move |window, cx| Tooltip::new(emoji.name).build(window, cx))
+					.tooltip(move |window, cx| Tooltip::new(label.clone()).build(window, cx))
 					.corner_radii(gpui::Corners::all(px(5f32)))
 					.cursor_pointer()
-					.child(pure_emoji);
+					.relative()
+					.child(shown)
+					.children(other_form);
 
 				if let Some(_) = emoji.skin_tones {
 					let popover_state = cx.global::<PopoverState>();
@@ -101,7 +133,7 @@ impl RenderOnce for EmojiRow {
 								if state.open_emoji == Some(emoji) {
 									return;
 								}
-								close_and_insert(pure_emoji, cx);
+								close_and_insert(toned_emoji, cx);
 							})
 							.into_any_element(),
 						selected: is_open,
@@ -124,7 +156,7 @@ impl RenderOnce for EmojiRow {
 				} else {
 					base_element
 						.on_click(move |_, _, cx: &mut App| {
-							close_and_insert(pure_emoji, cx);
+							close_and_insert(toned_emoji, cx);
 						})
 						.into_any_element()
 				}
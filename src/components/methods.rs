@@ -4,6 +4,7 @@ use emoji::Emoji;
 use gpui::{App, AppContext, BorrowAppContext, Context, Focusable, Window};
 use gpui_component::{
 	IndexPath,
+	input::{InputEvent, InputState},
 	list::{ListEvent, ListState},
 };
 use nonempty::NonEmpty;
@@ -12,11 +13,14 @@ use tracing::debug;
 use crate::{
 	components::{
 		list::types::EmojiListDelegate,
-		types::{Picker, PopoverState, SelectedEmoji},
+		types::{Picker, PopoverState, PreviewPresentation, SelectedEmoji, StagingTray},
 	},
 	emoji_sizing::calculate_emoji_sizing,
-	insert::close_and_insert,
-	keys::Quit,
+	insert::{
+		KeepOpen, RepeatCount, close_and_insert, close_and_insert_all, close_and_insert_as,
+		format::OutputFormat, insert_and_reopen,
+	},
+	keys::{Direction, Quit},
 };
 
 impl Picker {
@@ -26,13 +30,26 @@ impl Picker {
 
 		let sizing = calculate_emoji_sizing(container_width, rem_size);
 
-		let _last_selected = cx.default_global::<SelectedEmoji>().0.clone();
-
 		let body_focus_handle = cx.focus_handle();
 
 		let delegate =
 			EmojiListDelegate::new(sizing.emojis_per_row, sizing.emoji_size, body_focus_handle.clone());
-		let list_state = cx.new(|cx| ListState::new(delegate, window, cx).searchable(true));
+		let list_state = cx.new(|cx| ListState::new(delegate, window, cx));
+
+		// The picker owns the search input, rather than the list, so that a query
+		// can be put into it from outside.
+		let search_input = cx.new(|cx| InputState::new(window, cx).placeholder("Search..."));
+		let _search_subscription =
+			cx.subscribe_in(&search_input, window, |picker, input, ev: &InputEvent, window, cx| {
+				if let InputEvent::Change = ev {
+					let query = input.read(cx).value().trim().to_string();
+					picker.update_selection(window, cx, |delegate| {
+						delegate.update_search(&query);
+						let found = delegate.emoji_legions.iter().any(|legion| !legion.emojis.is_empty());
+						delegate.selected_index = found.then(IndexPath::default);
+					});
+				}
+			});
 
 		let _subscription = cx.subscribe(&list_state, |picker, _, ev: &ListEvent, cx| match ev {
 			ListEvent::Select(ix) => {
@@ -57,8 +74,10 @@ impl Picker {
 			body_focus_handle,
 			selected_emoji: None,
 			list_state,
+			search_input,
 			_padding: sizing.list_padding,
 			_subscription,
+			_search_subscription,
 		}
 	}
 
@@ -105,10 +124,93 @@ impl Picker {
 			.and_then(|ix| self.get_emoji_at_path(ix, cx));
 
 		if let Some(emoji) = selected_emoji {
-			close_and_insert(emoji.glyph, cx);
+			close_and_insert(emoji, cx);
 		}
 	}
 
+	pub(crate) fn select_current_keep_open(&self, _window: &mut Window, cx: &mut App) {
+		let selected_emoji = self
+			.list_state
+			.read(cx)
+			.delegate()
+			.selected_index
+			.and_then(|ix| self.get_emoji_at_path(ix, cx));
+
+		if let Some(emoji) = selected_emoji {
+			insert_and_reopen(emoji, cx);
+		}
+	}
+
+	pub(crate) fn toggle_keep_open(&self, cx: &mut App) {
+		let keep_open = cx.default_global::<KeepOpen>();
+		keep_open.0 = !keep_open.0;
+		debug!(keep_open = keep_open.0, "keep-open mode toggled");
+	}
+
+	pub(crate) fn toggle_presentation_preview(&self, cx: &mut App) {
+		let preview = cx.default_global::<PreviewPresentation>();
+		preview.0 = !preview.0;
+		debug!(preview = preview.0, "presentation preview toggled");
+	}
+
+	pub(crate) fn push_repeat_digit(&self, digit: u32, cx: &mut App) {
+		let count = cx.default_global::<RepeatCount>();
+		count.push_digit(digit);
+		debug!(count = count.0, "repeat count typed");
+	}
+
+	pub(crate) fn insert_current_as(&self, format: OutputFormat, _window: &mut Window, cx: &mut App) {
+		let selected_emoji = self
+			.list_state
+			.read(cx)
+			.delegate()
+			.selected_index
+			.and_then(|ix| self.get_emoji_at_path(ix, cx));
+
+		if let Some(emoji) = selected_emoji {
+			close_and_insert_as(emoji, format, cx);
+		}
+	}
+
+	pub(crate) fn stage_current(&self, _window: &mut Window, cx: &mut App) {
+		let selected_emoji = self
+			.list_state
+			.read(cx)
+			.delegate()
+			.selected_index
+			.and_then(|ix| self.get_emoji_at_path(ix, cx));
+
+		if let Some(emoji) = selected_emoji {
+			debug!(emoji = emoji.name, "emoji staged");
+			cx.update_global::<StagingTray, _>(|tray, _| tray.push(emoji.clone()));
+		}
+	}
+
+	pub(crate) fn move_tray_cursor(&self, direction: Direction, cx: &mut App) {
+		cx.update_global::<StagingTray, _>(|tray, _| tray.move_cursor(direction));
+	}
+
+	pub(crate) fn shift_staged(&self, direction: Direction, cx: &mut App) {
+		cx.update_global::<StagingTray, _>(|tray, _| tray.shift(direction));
+	}
+
+	pub(crate) fn remove_staged(&self, cx: &mut App) {
+		cx.update_global::<StagingTray, _>(|tray, _| tray.remove(tray.cursor));
+	}
+
+	/// Commits the staging tray when it holds anything, and otherwise behaves
+	/// like cancel.
+	pub(crate) fn confirm(&self, window: &mut Window, cx: &mut App) {
+		let staged = std::mem::take(&mut cx.global_mut::<StagingTray>().emojis);
+		let Some(staged) = NonEmpty::from_vec(staged) else {
+			self.cancel(window, cx);
+			return;
+		};
+
+		cx.global_mut::<StagingTray>().cursor = 0;
+		close_and_insert_all(staged, cx);
+	}
+
 	pub(crate) fn open_secondary(&self, _window: &mut Window, cx: &mut App) {
 		let selected_emoji = self
 			.list_state
@@ -126,8 +228,16 @@ impl Picker {
 		}
 	}
 
+	/// Types `query` into the search input, which filters the list as if the
+	/// user had typed it.
+	pub(crate) fn search_for(&self, query: &str, window: &mut Window, cx: &mut App) {
+		self.search_input.update(cx, |input, cx| {
+			input.set_value(query.to_string(), window, cx);
+		});
+	}
+
 	pub(crate) fn focus_search(&self, window: &mut Window, cx: &mut App) {
-		self.list_state.update(cx, |input, cx| {
+		self.search_input.update(cx, |input, cx| {
 			input.focus(window, cx);
 		});
 	}
@@ -141,7 +251,7 @@ impl Picker {
 			return;
 		}
 
-		if self.list_state.read(cx).focus_handle(cx).is_focused(window) {
+		if self.search_input.read(cx).focus_handle(cx).is_focused(window) {
 			self.body_focus_handle.focus(window);
 			return;
 		}
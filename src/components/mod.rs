@@ -5,6 +5,7 @@ pub(crate) mod render;
 pub(crate) mod types;
 
 pub(crate) mod list;
+pub(crate) mod tray;
 pub(crate) mod variants;
 
 pub(crate) use types::Picker;
@@ -1,16 +1,34 @@
 //! Picker rendering: the main picker view that composes the list and action
 //! handlers.
 
-use gpui::{Context, Edges, Focusable, InteractiveElement, IntoElement, ParentElement, Render, Styled, Window, px};
-use gpui_component::{StyledExt, gray_800, list::List, purple_400, v_flex};
+use gpui::{Context, Edges, Focusable, InteractiveElement, IntoElement, KeyContext, ParentElement, Render, Styled, Window, div, prelude::FluentBuilder, px};
+use gpui_component::{ActiveTheme, Icon, IconName, StyledExt, gray_800, input::Input, list::List, purple_400, v_flex};
 
-use crate::{components::types::{Picker, ToneIndex}, keys::*};
+use crate::{components::{tray::types::Tray, types::{Picker, StagingTray, ToneIndex}}, insert::RepeatCount, keys::*};
 
 impl Render for Picker {
 	fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
 		let mut emoji_edges = Edges::all(px(8.));
 		emoji_edges.right = px(-4.);
 
+		let staged = cx.global::<StagingTray>();
+		let tray = (!staged.emojis.is_empty()).then(|| Tray {
+			font_size: self.list_state.read(cx).delegate().emoji_size,
+			emojis:    staged.emojis.clone(),
+			cursor:    staged.cursor,
+		});
+
+		// The tray keys take over Alt+arrows and Alt+Backspace from the search
+		// input, so their context only exists while something is staged.
+		let mut key_context = KeyContext::default();
+		key_context.add("Picker");
+		if tray.is_some() {
+			key_context.add("Tray");
+		}
+
+		// A typed count shows at the end of the search input until it is used.
+		let pending_count = cx.try_global::<RepeatCount>().and_then(|count| count.0);
+
 		v_flex()
 			.bg(gray_800())
 			.text_color(purple_400())
@@ -37,6 +55,42 @@ impl Render for Picker {
 			.on_action(cx.listener(|this, _: &SelectCurrent, window, cx| {
 				this.select_current(window, cx);
 			}))
+			.on_action(cx.listener(|this, directive: &InsertAs, window, cx| {
+				this.insert_current_as(directive.format, window, cx);
+			}))
+			.on_action(cx.listener(|this, _: &SelectKeepOpen, window, cx| {
+				this.select_current_keep_open(window, cx);
+			}))
+			.on_action(cx.listener(|this, _: &ToggleKeepOpen, _, cx| {
+				this.toggle_keep_open(cx);
+			}))
+			.on_action(cx.listener(|this, _: &TogglePresentation, _, cx| {
+				this.toggle_presentation_preview(cx);
+				cx.notify();
+			}))
+			.on_action(cx.listener(|this, directive: &RepeatDigit, _, cx| {
+				this.push_repeat_digit(directive.digit, cx);
+				cx.notify();
+			}))
+			.on_action(cx.listener(|this, _: &StageCurrent, window, cx| {
+				this.stage_current(window, cx);
+				cx.notify();
+			}))
+			.on_action(cx.listener(|this, directive: &MoveTrayCursor, _, cx| {
+				this.move_tray_cursor(directive.direction.clone(), cx);
+				cx.notify();
+			}))
+			.on_action(cx.listener(|this, directive: &ShiftStaged, _, cx| {
+				this.shift_staged(directive.direction.clone(), cx);
+				cx.notify();
+			}))
+			.on_action(cx.listener(|this, _: &RemoveStaged, _, cx| {
+				this.remove_staged(cx);
+				cx.notify();
+			}))
+			.on_action(cx.listener(|this, _: &Confirm, window, cx| {
+				this.confirm(window, cx);
+			}))
 			.on_action(cx.listener(|this, _: &OpenSecondary, window, cx| {
 				this.open_secondary(window, cx);
 			}))
@@ -47,8 +101,22 @@ impl Render for Picker {
 				this.cancel(window, cx);
 			}))
 			.track_focus(&self.focus_handle(cx))
-			.key_context("Picker")
+			.key_context(key_context)
 			.size_full()
+			.children(tray)
+			.child(
+				// Inside the list's key context, so list bindings work while typing.
+				div().key_context("List").px_2().border_b_1().border_color(cx.theme().border).child(
+					Input::new(&self.search_input)
+						.prefix(Icon::new(IconName::Search).text_color(cx.theme().muted_foreground))
+						.cleanable(true)
+						.when_some(pending_count, |input, count| {
+							input.suffix(div().text_color(cx.theme().muted_foreground).child(format!("×{count}")))
+						})
+						.p_0()
+						.appearance(false),
+				),
+			)
 			.child(List::new(&self.list_state).scrollbar_visible(false).paddings(emoji_edges))
 	}
 }
@@ -0,0 +1,4 @@
+//! Staging tray for composing a sequence of emojis before inserting.
+
+pub(crate) mod render;
+pub(crate) mod types;
@@ -0,0 +1,34 @@
+//! Renders the staging tray above the emoji list.
+
+use gpui::{App, BorrowAppContext, InteractiveElement, IntoElement, ParentElement, RenderOnce, StatefulInteractiveElement, Styled, div, hsla, px};
+use gpui_component::h_flex;
+
+use crate::components::{tray::types::Tray, types::StagingTray};
+
+impl RenderOnce for Tray {
+	fn render(self, _window: &mut gpui::Window, _cx: &mut App) -> impl IntoElement {
+		let cursor = self.cursor;
+
+		h_flex().id("staging-tray").gap_1().p_1().bg(hsla(0., 0., 1., 0.05)).children(
+			self.emojis.into_iter().enumerate().map(move |(idx, staged)| {
+				let mut cell = div()
+					.id(("staged", idx))
+					.text_size(self.font_size)
+					.corner_radii(gpui::Corners::all(px(5f32)))
+					.cursor_pointer()
+					.hover(|s| s.bg(hsla(0., 0., 0., 0.1)))
+					.child(staged.glyph)
+					.on_click(move |_, _, cx: &mut App| {
+						cx.update_global::<StagingTray, _>(|tray, _| tray.remove(idx));
+						cx.refresh_windows();
+					});
+
+				if idx == cursor {
+					cell = cell.bg(hsla(0.0, 0.0, 1.0, 0.2));
+				}
+
+				cell
+			}),
+		)
+	}
+}
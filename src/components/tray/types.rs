@@ -0,0 +1,11 @@
+//! What the staging tray renders: a snapshot of the staged emojis and the
+//! cursor, taken from the `StagingTray` global each frame.
+
+use emoji::Emoji;
+use gpui::Pixels;
+
+pub struct Tray {
+	pub font_size: Pixels,
+	pub emojis:    Vec<Emoji>,
+	pub cursor:    usize,
+}
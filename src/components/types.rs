@@ -2,18 +2,20 @@
 
 use emoji::Emoji;
 use gpui::{Entity, FocusHandle, Pixels};
-use gpui_component::list::ListState;
+use gpui_component::{input::InputState, list::ListState};
 use nonempty::NonEmpty;
 
 use crate::components::list::types::EmojiListDelegate;
 
 pub(crate) struct Picker {
-	pub(crate) focus_handle:      FocusHandle,
-	pub(crate) body_focus_handle: FocusHandle,
-	pub(crate) selected_emoji:    Option<&'static Emoji>,
-	pub(crate) list_state:        Entity<ListState<EmojiListDelegate>>,
-	pub(crate) _padding:          Pixels,
-	pub(crate) _subscription:     gpui::Subscription,
+	pub(crate) focus_handle:         FocusHandle,
+	pub(crate) body_focus_handle:    FocusHandle,
+	pub(crate) selected_emoji:       Option<&'static Emoji>,
+	pub(crate) list_state:           Entity<ListState<EmojiListDelegate>>,
+	pub(crate) search_input:         Entity<InputState>,
+	pub(crate) _padding:             Pixels,
+	pub(crate) _subscription:        gpui::Subscription,
+	pub(crate) _search_subscription: gpui::Subscription,
 }
 
 impl gpui::Focusable for Picker {
@@ -47,6 +49,60 @@ impl Default for ToneIndex {
 	fn default() -> Self { Self(0) }
 }
 
+/// Whether cells show emoji in the configured presentation rather than as the
+/// emoji data spells them.
+#[derive(Default)]
+pub(crate) struct PreviewPresentation(pub bool);
+impl gpui::Global for PreviewPresentation {}
+
+/// Emojis staged for insertion as one sequence, with the position that the
+/// reorder and delete actions operate on.
+#[derive(Default)]
+pub(crate) struct StagingTray {
+	pub emojis: Vec<Emoji>,
+	pub cursor: usize,
+}
+impl gpui::Global for StagingTray {}
+
+impl StagingTray {
+	pub fn push(&mut self, emoji: Emoji) {
+		self.emojis.push(emoji);
+		self.cursor = self.emojis.len() - 1;
+	}
+
+	pub fn remove(&mut self, ix: usize) {
+		if ix < self.emojis.len() {
+			self.emojis.remove(ix);
+		}
+		self.cursor = self.cursor.min(self.emojis.len().saturating_sub(1));
+	}
+
+	pub fn move_cursor(&mut self, direction: crate::keys::Direction) {
+		use crate::keys::Direction;
+		self.cursor = match direction {
+			Direction::Forward => (self.cursor + 1).min(self.emojis.len().saturating_sub(1)),
+			Direction::Backward => self.cursor.saturating_sub(1),
+		};
+	}
+
+	/// Swaps the emoji under the cursor with its neighbour, keeping the cursor on
+	/// it.
+	pub fn shift(&mut self, direction: crate::keys::Direction) {
+		use crate::keys::Direction;
+		let target = match direction {
+			Direction::Forward => self.cursor + 1,
+			Direction::Backward => match self.cursor.checked_sub(1) {
+				Some(target) => target,
+				None => return,
+			},
+		};
+		if target < self.emojis.len() {
+			self.emojis.swap(self.cursor, target);
+			self.cursor = target;
+		}
+	}
+}
+
 #[derive(Clone, Copy)]
 pub(crate) struct PopoverState {
 	pub open_emoji: Option<&'static Emoji>,
@@ -57,3 +113,93 @@ impl Default for PopoverState {
 }
 
 impl gpui::Global for PopoverState {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		keys::Direction::{Backward, Forward},
+		lookup::by_glyph,
+	};
+
+	fn tray(glyphs: &[&str], cursor: usize) -> StagingTray {
+		let emojis = glyphs
+			.iter()
+			.map(|glyph| by_glyph(glyph).unwrap_or_else(|| panic!("{glyph} is missing")).clone())
+			.collect();
+		StagingTray { emojis, cursor }
+	}
+
+	fn glyphs(tray: &StagingTray) -> Vec<&str> {
+		tray.emojis.iter().map(|emoji| emoji.glyph).collect()
+	}
+
+	#[test]
+	fn an_empty_tray_keeps_its_cursor_at_the_start() {
+		let mut tray = StagingTray::default();
+		tray.move_cursor(Forward);
+		tray.move_cursor(Backward);
+		tray.shift(Forward);
+		tray.shift(Backward);
+		tray.remove(0);
+
+		assert!(tray.emojis.is_empty());
+		assert_eq!(tray.cursor, 0);
+	}
+
+	#[test]
+	fn pushing_puts_the_cursor_on_the_new_emoji() {
+		let mut tray = tray(&["🦀"], 0);
+		tray.push(by_glyph("🐍").unwrap().clone());
+
+		assert_eq!(glyphs(&tray), ["🦀", "🐍"]);
+		assert_eq!(tray.cursor, 1);
+	}
+
+	#[test]
+	fn removing_at_the_end_pulls_the_cursor_back() {
+		let mut tray = tray(&["🦀", "🐍", "🐹"], 2);
+
+		tray.remove(2);
+		assert_eq!(glyphs(&tray), ["🦀", "🐍"]);
+		assert_eq!(tray.cursor, 1);
+
+		// Out of range removes nothing.
+		tray.remove(5);
+		assert_eq!(glyphs(&tray), ["🦀", "🐍"]);
+
+		tray.remove(0);
+		tray.remove(0);
+		assert!(tray.emojis.is_empty());
+		assert_eq!(tray.cursor, 0);
+	}
+
+	#[test]
+	fn the_cursor_stops_at_either_end() {
+		let mut tray = tray(&["🦀", "🐍"], 0);
+
+		tray.move_cursor(Backward);
+		assert_eq!(tray.cursor, 0);
+		tray.move_cursor(Forward);
+		tray.move_cursor(Forward);
+		assert_eq!(tray.cursor, 1);
+	}
+
+	#[test]
+	fn shifting_past_either_end_changes_nothing() {
+		let mut tray = tray(&["🦀", "🐍", "🐹"], 0);
+
+		tray.shift(Backward);
+		assert_eq!(glyphs(&tray), ["🦀", "🐍", "🐹"]);
+		assert_eq!(tray.cursor, 0);
+
+		tray.shift(Forward);
+		tray.shift(Forward);
+		assert_eq!(glyphs(&tray), ["🐍", "🐹", "🦀"]);
+		assert_eq!(tray.cursor, 2);
+
+		tray.shift(Forward);
+		assert_eq!(glyphs(&tray), ["🐍", "🐹", "🦀"]);
+		assert_eq!(tray.cursor, 2);
+	}
+}
@@ -21,7 +21,7 @@ impl RenderOnce for Variants {
 					.id("hi")
 					.hover(|s| s.bg(hsla(0., 0., 0., 0.1)))
 					.on_click(move |_, _, cx: &mut App| {
-						close_and_insert(variant.glyph, cx);
+						close_and_insert(&variant, cx);
 					})
 			}))
 			.into_any_element()
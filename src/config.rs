@@ -0,0 +1,93 @@
+//! User configuration, read once from `$XDG_CONFIG_HOME/bob/config.json`.
+
+use std::{collections::BTreeMap, path::PathBuf, sync::LazyLock};
+
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::insert::{
+	backend::BackendKind,
+	format::{OutputFormat, Presentation},
+	notify::Notify,
+	rules::AppRule,
+	selection::PrimarySelection,
+};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct Config {
+	/// How a selected emoji is written into the target application.
+	pub output_format:        OutputFormat,
+	/// Template each picked emoji is written through, e.g. `"{glyph} "`; it
+	/// takes precedence over the output format. See `format::render_template`.
+	pub template:             Option<String>,
+	/// Variation selector given to emoji that also have a text presentation.
+	pub presentation:         Presentation,
+	/// Start with the picker showing each emoji in that presentation.
+	pub preview_presentation: bool,
+	/// Per-application overrides, matched against the target window.
+	pub rules:                Vec<AppRule>,
+	/// Start with the picker reopening after every insertion.
+	pub keep_open:            bool,
+	/// Insertion backends to try, in order; empty means the built-in chain.
+	pub backends:             Vec<BackendKind>,
+	/// Hide clipboard-only fallback copies from clipboard managers too, not just
+	/// the transient copies made while pasting.
+	pub mark_fallback_copies: bool,
+	/// Put inserted text on the PRIMARY selection as well, for middle-click.
+	pub primary_selection:    PrimarySelection,
+	/// Start the search with the currently selected text.
+	pub query_from_primary:   bool,
+	/// Which insertion outcomes raise a desktop notification.
+	pub notify:               Notify,
+	/// Text inserted straight from a global hotkey, keyed by shortcut, e.g.
+	/// `"ctrl+alt+1": "👍"`. Only the service registers them.
+	pub favorites:            BTreeMap<String, String>,
+}
+
+pub(crate) static CONFIG: LazyLock<Config> = LazyLock::new(load);
+
+/// Directory holding `config.json`, following the XDG base directory spec on
+/// every platform so there is one place to look.
+pub(crate) fn config_dir() -> Option<PathBuf> {
+	std::env::var_os("XDG_CONFIG_HOME")
+		.map(PathBuf::from)
+		.or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+		.map(|dir| dir.join("bob"))
+}
+
+/// Directory for state the app writes itself, such as portal restore tokens,
+/// kept apart from the user's config.
+pub(crate) fn state_dir() -> Option<PathBuf> {
+	std::env::var_os("XDG_STATE_HOME")
+		.map(PathBuf::from)
+		.or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+		.map(|dir| dir.join("bob"))
+}
+
+fn load() -> Config {
+	let Some(path) = config_dir().map(|dir| dir.join("config.json")) else {
+		warn!("no config directory could be determined; using defaults");
+		return Config::default();
+	};
+
+	let contents = match std::fs::read_to_string(&path) {
+		Ok(contents) => contents,
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Config::default(),
+		Err(e) => {
+			warn!(path = %path.display(), "failed to read config: {e}");
+			return Config::default();
+		}
+	};
+
+	match serde_json::from_str(&contents) {
+		Ok(config) => {
+			info!(path = %path.display(), "loaded config");
+			config
+		}
+		Err(e) => {
+			warn!(path = %path.display(), "failed to parse config, using defaults: {e}");
+			Config::default()
+		}
+	}
+}
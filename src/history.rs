@@ -0,0 +1,185 @@
+//! Recently picked emoji, newest first, kept in the state directory so the
+//! re-insert hotkey and the `reinsert` command see the same list across runs.
+//! Each entry is one pick, as `SelectedEmoji` holds it: a single emoji or a
+//! staged sequence, stored by glyph and rendered afresh when re-inserted.
+
+use std::{
+	fs::{File, OpenOptions},
+	io::{self, Read, Seek, Write},
+	path::{Path, PathBuf},
+};
+
+use emoji::Emoji;
+use tracing::warn;
+
+use crate::{config::state_dir, lookup::by_glyph};
+
+const HISTORY_FILE: &str = "history.json";
+
+/// Entries kept; older ones fall off the end.
+const MAX_ENTRIES: usize = 32;
+
+fn history_path() -> Option<PathBuf> { state_dir().map(|dir| dir.join(HISTORY_FILE)) }
+
+/// Glyphs of one pick.
+type Entry = Vec<String>;
+
+/// Reads the entries from a locked history file; a new, empty file has none.
+fn read_entries(file: &mut File, path: &Path) -> io::Result<Vec<Entry>> {
+	let mut contents = String::new();
+	file.read_to_string(&mut contents)?;
+	if contents.trim().is_empty() {
+		return Ok(Vec::new());
+	}
+	Ok(serde_json::from_str(&contents).unwrap_or_else(|e| {
+		warn!(path = %path.display(), "failed to parse history, starting afresh: {e}");
+		Vec::new()
+	}))
+}
+
+fn load() -> Vec<Entry> {
+	let Some(path) = history_path() else {
+		return Vec::new();
+	};
+
+	let read = File::open(&path).and_then(|mut file| {
+		file.lock_shared()?;
+		read_entries(&mut file, &path)
+	});
+	match read {
+		Ok(entries) => entries,
+		Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+		Err(e) => {
+			warn!(path = %path.display(), "failed to read history: {e}");
+			Vec::new()
+		}
+	}
+}
+
+/// Moves the pick to the front of the history, so repeating an insertion does
+/// not push everything else out.
+pub(crate) fn record(picked: &[Emoji]) {
+	if picked.is_empty() {
+		return;
+	}
+
+	let Some(path) = history_path() else {
+		warn!("no state directory could be determined; history not saved");
+		return;
+	};
+
+	let entry: Entry = picked.iter().map(|emoji| emoji.glyph.to_string()).collect();
+	let saved = update(&path, |entries| {
+		entries.retain(|existing| *existing != entry);
+		entries.insert(0, entry);
+		entries.truncate(MAX_ENTRIES);
+	});
+	if let Err(e) = saved {
+		warn!(path = %path.display(), "failed to save history: {e}");
+	}
+}
+
+/// Rewrites the history under an exclusive lock on the file, so insertions
+/// that finish at the same time, in this process or another, keep each
+/// other's entries.
+fn update(path: &Path, change: impl FnOnce(&mut Vec<Entry>)) -> io::Result<()> {
+	if let Some(dir) = path.parent() {
+		std::fs::create_dir_all(dir)?;
+	}
+	let mut file =
+		OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+	file.lock()?;
+
+	let mut entries = read_entries(&mut file, path)?;
+	change(&mut entries);
+
+	let contents = serde_json::to_string(&entries).map_err(io::Error::other)?;
+	file.set_len(0)?;
+	file.rewind()?;
+	file.write_all(contents.as_bytes())
+}
+
+/// The `n`-th most recent pick, counting from 1; `None` when there is no such
+/// pick or its emoji are no longer known.
+pub(crate) fn nth(n: usize) -> Option<Vec<&'static Emoji>> {
+	let entry = load().into_iter().nth(n.checked_sub(1)?)?;
+	let picked: Option<Vec<_>> = entry.iter().map(|glyph| by_glyph(glyph)).collect();
+	picked.filter(|picked| !picked.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::thread;
+
+	use super::*;
+	use crate::test_support::{ScopedEnv, lock_env};
+
+	const FAMILY: &str = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+	const TECHNOLOGIST: &str = "\u{1F469}\u{1F3FD}\u{200D}\u{1F4BB}";
+	const GERMANY: &str = "\u{1F1E9}\u{1F1EA}";
+
+	fn pick(glyphs: &[&str]) -> Vec<Emoji> {
+		glyphs.iter().map(|glyph| by_glyph(glyph).expect("emoji is known").clone()).collect()
+	}
+
+	fn glyphs(n: usize) -> Option<Vec<&'static str>> {
+		nth(n).map(|picked| picked.iter().map(|emoji| emoji.glyph).collect())
+	}
+
+	/// Points the state directory at a fresh directory for one test.
+	fn fresh_state(name: &str) -> (ScopedEnv, PathBuf) {
+		let state_home =
+			std::env::temp_dir().join(format!("bob-history-{name}-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&state_home);
+		let mut env = ScopedEnv::default();
+		env.set("XDG_STATE_HOME", &state_home);
+		(env, state_home)
+	}
+
+	#[test]
+	fn keeps_picks_newest_first() {
+		let _lock = lock_env();
+		let (_env, state_home) = fresh_state("order");
+
+		record(&pick(&[FAMILY]));
+		record(&pick(&[TECHNOLOGIST, GERMANY]));
+		assert_eq!(glyphs(1), Some(vec![TECHNOLOGIST, GERMANY]));
+		assert_eq!(glyphs(2), Some(vec![FAMILY]));
+		assert_eq!(glyphs(3), None);
+		assert_eq!(glyphs(0), None);
+
+		// Picking again moves the pick to the front rather than adding it twice.
+		record(&pick(&[FAMILY]));
+		assert_eq!(glyphs(1), Some(vec![FAMILY]));
+		assert_eq!(glyphs(2), Some(vec![TECHNOLOGIST, GERMANY]));
+		assert_eq!(glyphs(3), None);
+
+		let _ = std::fs::remove_dir_all(&state_home);
+	}
+
+	#[test]
+	fn concurrent_records_keep_every_pick() {
+		let _lock = lock_env();
+		let (_env, state_home) = fresh_state("concurrent");
+
+		// Thirty different picks, ten from each thread, all fit in the history.
+		let recorders: Vec<_> = [FAMILY, TECHNOLOGIST, GERMANY]
+			.into_iter()
+			.map(|glyph| {
+				thread::spawn(move || {
+					for count in 1..=10 {
+						record(&pick(&vec![glyph; count]));
+					}
+				})
+			})
+			.collect();
+		for recorder in recorders {
+			recorder.join().unwrap();
+		}
+
+		assert!(glyphs(30).is_some(), "every pick is kept");
+		assert_eq!(glyphs(31), None);
+
+		let _ = std::fs::remove_dir_all(&state_home);
+	}
+}
@@ -1,20 +1,114 @@
 //! Ideal emoji insertion via Enigo (macOS and X11).
 
-#[cfg(not(target_os = "linux"))]
 use enigo::{Enigo, Keyboard, Settings};
+use tracing::warn;
+
+use super::{
+	FOCUS_TIMEOUT,
+	backend::{BackendKind, InsertBackend, InsertError, InsertTarget, Outcome},
+};
+
+/// Types the text as synthetic key events into whatever has focus.
+pub(crate) struct EnigoBackend;
+
+impl InsertBackend for EnigoBackend {
+	fn kind(&self) -> BackendKind { BackendKind::Enigo }
+
+	fn available(&self) -> bool { session_supported() }
+
+	fn insert(&self, text: &str, target: &InsertTarget) -> Result<Outcome, InsertError> {
+		restore_focus(target)?;
+		insert_enigo(text)?;
+		Ok(Outcome::Inserted)
+	}
+}
+
+/// On Linux, synthetic input only reaches other clients under X11.
+#[cfg(target_os = "linux")]
+fn session_supported() -> bool {
+	use crate::integration::linux::{LinuxSession, detect_linux_session};
+	detect_linux_session() == LinuxSession::X11
+}
+
+#[cfg(not(target_os = "linux"))]
+fn session_supported() -> bool { true }
+
+/// Reactivates the window captured when the picker opened, or waits for focus
+/// to leave the picker on its own when none was.
+#[cfg(target_os = "linux")]
+pub(crate) fn restore_focus(target: &InsertTarget) -> Result<(), InsertError> {
+	use crate::integration::x11;
+
+	let Some(window) = target.x11_window else {
+		let moved = x11::wait_for_focus_elsewhere(FOCUS_TIMEOUT)
+			.map_err(|e| InsertError::Failed(format!("could not read the active window: {e}")))?;
+		if !moved {
+			warn!("focus did not leave the picker; typing anyway");
+		}
+		return Ok(());
+	};
 
-pub(crate) fn insert_enigo(emoji: &str) {
-	#[cfg(not(target_os = "linux"))]
-	{
-		
+	x11::activate_window(window)
+		.map_err(|e| InsertError::Failed(format!("could not reactivate the target window: {e}")))
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn restore_focus(_target: &InsertTarget) -> Result<(), InsertError> {
+	use crate::integration::macos;
+
+	if !super::wait_until(FOCUS_TIMEOUT, macos::frontmost_is_elsewhere) {
+		warn!("focus did not leave the picker; typing anyway");
+	}
+	Ok(())
+}
+
+pub(crate) fn insert_enigo(emoji: &str) -> Result<(), InsertError> {
+	let mut enigo = Enigo::new(&Settings::default())
+		.map_err(|e| InsertError::Failed(format!("could not connect to input: {e}")))?;
+	enigo.text(emoji).map_err(|e| InsertError::Failed(format!("could not type text: {e}")))
+}
 
-		let mut enigo = Enigo::new(&Settings::default()).unwrap();
-		enigo.text(emoji).unwrap();
+/// Presses a paste shortcut through XTest.
+#[cfg(target_os = "linux")]
+pub(crate) fn press_shortcut_enigo(
+	shortcut: super::rules::PasteShortcut,
+) -> Result<(), InsertError> {
+	use enigo::{Direction, Key};
+
+	use super::rules::PasteShortcut;
+
+	let (modifiers, key): (&[Key], Key) = match shortcut {
+		PasteShortcut::CtrlV => (&[Key::Control], Key::Unicode('v')),
+		PasteShortcut::CtrlShiftV => (&[Key::Control, Key::Shift], Key::Unicode('v')),
+		PasteShortcut::ShiftInsert => (&[Key::Shift], Key::Insert),
+	};
+
+	let mut enigo = Enigo::new(&Settings::default())
+		.map_err(|e| InsertError::Failed(format!("could not connect to input: {e}")))?;
+	let failed =
+		|e: enigo::InputError| InsertError::Failed(format!("could not send paste shortcut: {e}"));
+
+	for modifier in modifiers {
+		enigo.key(*modifier, Direction::Press).map_err(failed)?;
 	}
+	enigo.key(key, Direction::Click).map_err(failed)?;
+	for modifier in modifiers.iter().rev() {
+		enigo.key(*modifier, Direction::Release).map_err(failed)?;
+	}
+	Ok(())
+}
+
+/// Presses BackSpace `count` times through XTest.
+#[cfg(target_os = "linux")]
+pub(crate) fn press_backspace_enigo(count: usize) -> Result<(), InsertError> {
+	use enigo::{Direction, Key};
 
-	#[cfg(target_os = "linux")]
-	{
-		let mut enigo = enigo::Enigo::new(&enigo::Settings::default()).unwrap();
-		enigo::Keyboard::text(&mut enigo, emoji).unwrap();
+	let mut enigo = Enigo::new(&Settings::default())
+		.map_err(|e| InsertError::Failed(format!("could not connect to input: {e}")))?;
+	for _ in 0..count {
+		enigo
+			.key(Key::Backspace, Direction::Click)
+			.map_err(|e| InsertError::Failed(format!("could not send BackSpace: {e}")))?;
 	}
+	Ok(())
 }
@@ -0,0 +1,279 @@
+//! The insertion backend abstraction and the fallback chain that drives it.
+
+use std::fmt;
+
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+/// Where an insertion is headed. Only Linux captures anything about it.
+#[cfg(target_os = "linux")]
+pub(crate) type InsertTarget = crate::integration::linux::PendingInsertTarget;
+
+#[cfg(not(target_os = "linux"))]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct InsertTarget;
+
+#[cfg(not(target_os = "linux"))]
+impl gpui::Global for InsertTarget {}
+
+/// Names a backend in the `backends` priority list of the config.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum BackendKind {
+	Enigo,
+	InputMethod,
+	HyprlandPaste,
+	I3Paste,
+	NiriType,
+	VirtualKeyboard,
+	PortalPaste,
+	UinputPaste,
+	X11Paste,
+	Clipboard,
+}
+
+impl BackendKind {
+	/// The order tried when the config does not name one. Backends that do not
+	/// apply to the running session rule themselves out when probed. The input
+	/// method commits text without touching the clipboard, so it goes ahead of
+	/// the compositor paste backends; it gives way to them when no text field
+	/// takes input method focus.
+	pub(crate) const DEFAULT_CHAIN: &[BackendKind] = &[
+		BackendKind::InputMethod,
+		BackendKind::HyprlandPaste,
+		BackendKind::I3Paste,
+		BackendKind::NiriType,
+		BackendKind::VirtualKeyboard,
+		BackendKind::PortalPaste,
+		BackendKind::UinputPaste,
+		BackendKind::X11Paste,
+		BackendKind::Enigo,
+		BackendKind::Clipboard,
+	];
+}
+
+impl fmt::Display for BackendKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Enigo => "enigo",
+			Self::InputMethod => "input-method",
+			Self::HyprlandPaste => "hyprland-paste",
+			Self::I3Paste => "i3-paste",
+			Self::NiriType => "niri-type",
+			Self::VirtualKeyboard => "virtual-keyboard",
+			Self::PortalPaste => "portal-paste",
+			Self::UinputPaste => "uinput-paste",
+			Self::X11Paste => "x11-paste",
+			Self::Clipboard => "clipboard",
+		})
+	}
+}
+
+/// What a successful backend achieved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Outcome {
+	/// The text landed in the target application.
+	Inserted,
+	/// The text is only on the clipboard; the user still has to paste it.
+	Copied,
+}
+
+#[derive(Debug)]
+pub(crate) enum InsertError {
+	/// The backend cannot serve this particular insertion, e.g. no target window
+	/// was captured.
+	Unsupported(String),
+	/// The backend tried and failed.
+	Failed(String),
+	/// The captured target window has been closed. Any later backend would
+	/// insert into whatever window has focus instead, so the chain stops.
+	TargetClosed,
+	/// Every backend in the chain was unavailable or failed.
+	Exhausted,
+}
+
+impl fmt::Display for InsertError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Unsupported(reason) => write!(f, "unsupported: {reason}"),
+			Self::Failed(reason) => write!(f, "failed: {reason}"),
+			Self::TargetClosed => f.write_str("the target window has been closed"),
+			Self::Exhausted => f.write_str("no insertion backend succeeded"),
+		}
+	}
+}
+
+impl std::error::Error for InsertError {}
+
+pub(crate) trait InsertBackend {
+	fn kind(&self) -> BackendKind;
+
+	/// Probes whether the backend can work in the running session at all.
+	fn available(&self) -> bool;
+
+	fn insert(&self, text: &str, target: &InsertTarget) -> Result<Outcome, InsertError>;
+}
+
+/// Tries each available backend in order, moving on to the next whenever one
+/// fails, and reports which backend finally succeeded. Stops early once the
+/// target window turns out to be closed.
+pub(crate) fn insert_with_fallback(
+	chain: &[Box<dyn InsertBackend>],
+	text: &str,
+	target: &InsertTarget,
+) -> Result<(BackendKind, Outcome), InsertError> {
+	for backend in chain {
+		let kind = backend.kind();
+		if !backend.available() {
+			debug!(%kind, "insertion backend unavailable; skipping");
+			continue;
+		}
+
+		match backend.insert(text, target) {
+			Ok(outcome) => {
+				debug!(%kind, ?outcome, "insertion backend succeeded");
+				return Ok((kind, outcome));
+			}
+			Err(InsertError::TargetClosed) => {
+				warn!(%kind, "the target window has been closed; not inserting anywhere else");
+				return Err(InsertError::TargetClosed);
+			}
+			Err(e) => warn!(%kind, "insertion backend {e}; trying the next one"),
+		}
+	}
+
+	Err(InsertError::Exhausted)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{cell::RefCell, rc::Rc};
+
+	use super::*;
+
+	type Reply = fn() -> Result<Outcome, InsertError>;
+
+	/// A backend that answers every insertion the same way and logs the attempt.
+	struct Fake {
+		kind:      BackendKind,
+		available: bool,
+		reply:     Reply,
+		tried:     Rc<RefCell<Vec<BackendKind>>>,
+	}
+
+	impl InsertBackend for Fake {
+		fn kind(&self) -> BackendKind { self.kind }
+
+		fn available(&self) -> bool { self.available }
+
+		fn insert(&self, _text: &str, _target: &InsertTarget) -> Result<Outcome, InsertError> {
+			self.tried.borrow_mut().push(self.kind);
+			(self.reply)()
+		}
+	}
+
+	/// Runs the chain described by `backends` and returns the result together
+	/// with the backends that were asked to insert, in order.
+	fn run(
+		backends: &[(BackendKind, bool, Reply)],
+	) -> (Result<(BackendKind, Outcome), InsertError>, Vec<BackendKind>) {
+		let tried = Rc::new(RefCell::new(Vec::new()));
+		let chain: Vec<Box<dyn InsertBackend>> = backends
+			.iter()
+			.map(|&(kind, available, reply)| {
+				Box::new(Fake { kind, available, reply, tried: tried.clone() }) as Box<dyn InsertBackend>
+			})
+			.collect();
+
+		let result = insert_with_fallback(&chain, "🔥", &InsertTarget::default());
+		(result, tried.take())
+	}
+
+	fn inserted() -> Result<Outcome, InsertError> { Ok(Outcome::Inserted) }
+
+	fn copied() -> Result<Outcome, InsertError> { Ok(Outcome::Copied) }
+
+	fn unsupported() -> Result<Outcome, InsertError> { Err(InsertError::Unsupported("fake".into())) }
+
+	fn failed() -> Result<Outcome, InsertError> { Err(InsertError::Failed("fake".into())) }
+
+	fn closed() -> Result<Outcome, InsertError> { Err(InsertError::TargetClosed) }
+
+	#[test]
+	fn the_input_method_precedes_paste_backends() {
+		let position =
+			|kind| BackendKind::DEFAULT_CHAIN.iter().position(|&k| k == kind).expect("in the chain");
+		for paste in [
+			BackendKind::HyprlandPaste,
+			BackendKind::I3Paste,
+			BackendKind::PortalPaste,
+			BackendKind::UinputPaste,
+			BackendKind::X11Paste,
+		] {
+			assert!(position(BackendKind::InputMethod) < position(paste), "{paste} comes first");
+		}
+	}
+
+	#[test]
+	fn unavailable_backends_are_not_tried() {
+		let (result, tried) =
+			run(&[(BackendKind::Enigo, false, inserted), (BackendKind::Clipboard, true, copied)]);
+		assert!(matches!(result, Ok((BackendKind::Clipboard, Outcome::Copied))));
+		assert_eq!(tried, [BackendKind::Clipboard]);
+	}
+
+	#[test]
+	fn unsupported_skips_to_the_next_backend() {
+		let (result, tried) =
+			run(&[(BackendKind::X11Paste, true, unsupported), (BackendKind::Enigo, true, inserted)]);
+		assert!(matches!(result, Ok((BackendKind::Enigo, Outcome::Inserted))));
+		assert_eq!(tried, [BackendKind::X11Paste, BackendKind::Enigo]);
+	}
+
+	#[test]
+	fn failed_continues_with_the_next_backend() {
+		let (result, tried) = run(&[
+			(BackendKind::VirtualKeyboard, true, failed),
+			(BackendKind::UinputPaste, true, failed),
+			(BackendKind::Enigo, true, inserted),
+		]);
+		assert!(matches!(result, Ok((BackendKind::Enigo, Outcome::Inserted))));
+		assert_eq!(tried, [BackendKind::VirtualKeyboard, BackendKind::UinputPaste, BackendKind::Enigo]);
+	}
+
+	#[test]
+	fn copied_ends_the_chain() {
+		let (result, tried) =
+			run(&[(BackendKind::Clipboard, true, copied), (BackendKind::Enigo, true, inserted)]);
+		assert!(matches!(result, Ok((BackendKind::Clipboard, Outcome::Copied))));
+		assert_eq!(tried, [BackendKind::Clipboard]);
+	}
+
+	#[test]
+	fn a_closed_target_ends_the_chain() {
+		let (result, tried) = run(&[
+			(BackendKind::HyprlandPaste, true, closed),
+			(BackendKind::VirtualKeyboard, true, inserted),
+		]);
+		assert!(matches!(result, Err(InsertError::TargetClosed)));
+		assert_eq!(tried, [BackendKind::HyprlandPaste]);
+	}
+
+	#[test]
+	fn exhausted_when_every_backend_declines() {
+		let (result, tried) = run(&[
+			(BackendKind::InputMethod, false, inserted),
+			(BackendKind::X11Paste, true, unsupported),
+			(BackendKind::Enigo, true, failed),
+		]);
+		assert!(matches!(result, Err(InsertError::Exhausted)));
+		assert_eq!(tried, [BackendKind::X11Paste, BackendKind::Enigo]);
+	}
+
+	#[test]
+	fn empty_chain_is_exhausted() {
+		let (result, tried) = run(&[]);
+		assert!(matches!(result, Err(InsertError::Exhausted)));
+		assert!(tried.is_empty());
+	}
+}
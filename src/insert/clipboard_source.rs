@@ -0,0 +1,212 @@
+//! A clipboard selection served from this process over `ext_data_control_v1`,
+//! or `zwlr_data_control_v1` where only that is offered, so a paste can be
+//! confirmed by the target actually asking for the data.
+
+use std::{
+	fs::File,
+	io::Write,
+	sync::mpsc,
+	thread,
+	time::{Duration, Instant},
+};
+
+use tracing::debug;
+use wayland_client::{
+	Connection, Dispatch, EventQueue, QueueHandle, delegate_noop, event_created_child,
+	globals::{GlobalListContents, registry_queue_init},
+	protocol::{wl_registry, wl_seat},
+};
+use wayland_protocols::ext::data_control::v1::client::{
+	ext_data_control_device_v1::{self, ExtDataControlDeviceV1},
+	ext_data_control_manager_v1::ExtDataControlManagerV1,
+	ext_data_control_offer_v1::ExtDataControlOfferV1,
+	ext_data_control_source_v1::{self, ExtDataControlSourceV1},
+};
+use wayland_protocols_wlr::data_control::v1::client::{
+	zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+	zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+	zwlr_data_control_offer_v1::ZwlrDataControlOfferV1,
+	zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+};
+
+use super::backend::InsertError;
+use crate::integration::linux::CLIPBOARD_MANAGER_HINT;
+
+pub(crate) const TEXT_MIME_TYPES: &[&str] =
+	&["text/plain;charset=utf-8", "text/plain", "UTF8_STRING", "TEXT", "STRING"];
+
+/// The clipboard selection while this process owns it. It is served from a
+/// background thread until another client replaces it.
+pub(crate) struct ClipboardSource {
+	/// When each request for the text came in.
+	requests: mpsc::Receiver<Instant>,
+}
+
+impl ClipboardSource {
+	/// Waits for someone to ask for the contents after `since`; `false` on
+	/// timeout. Earlier requests, e.g. by clipboard managers reacting to the
+	/// new selection, are not the paste.
+	pub(crate) fn wait_for_request_since(&self, since: Instant, timeout: Duration) -> bool {
+		let deadline = Instant::now() + timeout;
+		loop {
+			let left = deadline.saturating_duration_since(Instant::now());
+			match self.requests.recv_timeout(left) {
+				Ok(requested) if requested >= since => return true,
+				Ok(_) => {}
+				Err(_) => return false,
+			}
+		}
+	}
+}
+
+struct State {
+	contents:  Vec<u8>,
+	requests:  mpsc::Sender<Instant>,
+	cancelled: bool,
+}
+
+impl State {
+	fn send(&self, mime_type: &str, mut file: File) {
+		let hint = mime_type == CLIPBOARD_MANAGER_HINT;
+		let data: &[u8] = if hint { b"secret" } else { &self.contents };
+		if let Err(e) = file.write_all(data) {
+			debug!(%mime_type, "clipboard request was abandoned: {e}");
+		}
+		if !hint {
+			let _ = self.requests.send(Instant::now());
+		}
+	}
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+	fn event(
+		_: &mut Self,
+		_: &wl_registry::WlRegistry,
+		_: wl_registry::Event,
+		_: &GlobalListContents,
+		_: &Connection,
+		_: &QueueHandle<Self>,
+	) {
+	}
+}
+
+/// ext-data-control is the standardised wlr-data-control, with the same
+/// requests and events under other names.
+macro_rules! data_control_dispatch {
+	($source_mod:ident, $source:ty, $device_mod:ident, $device:ty, $offer:ty, $manager:ty) => {
+		impl Dispatch<$source, ()> for State {
+			fn event(
+				state: &mut Self,
+				source: &$source,
+				event: $source_mod::Event,
+				_: &(),
+				_: &Connection,
+				_: &QueueHandle<Self>,
+			) {
+				match event {
+					$source_mod::Event::Send { mime_type, fd } => state.send(&mime_type, File::from(fd)),
+					$source_mod::Event::Cancelled => {
+						source.destroy();
+						state.cancelled = true;
+					}
+					_ => {}
+				}
+			}
+		}
+
+		impl Dispatch<$device, ()> for State {
+			event_created_child!(State, $device, [
+				$device_mod::EVT_DATA_OFFER_OPCODE => ($offer, ()),
+			]);
+
+			fn event(
+				_: &mut Self,
+				_: &$device,
+				event: $device_mod::Event,
+				_: &(),
+				_: &Connection,
+				_: &QueueHandle<Self>,
+			) {
+				// Offers of other clients' selections are of no use here.
+				match event {
+					$device_mod::Event::Selection { id: Some(offer) }
+					| $device_mod::Event::PrimarySelection { id: Some(offer) } => offer.destroy(),
+					_ => {}
+				}
+			}
+		}
+
+		delegate_noop!(State: ignore $offer);
+		delegate_noop!(State: $manager);
+	};
+}
+
+data_control_dispatch!(
+	ext_data_control_source_v1,
+	ExtDataControlSourceV1,
+	ext_data_control_device_v1,
+	ExtDataControlDeviceV1,
+	ExtDataControlOfferV1,
+	ExtDataControlManagerV1
+);
+data_control_dispatch!(
+	zwlr_data_control_source_v1,
+	ZwlrDataControlSourceV1,
+	zwlr_data_control_device_v1,
+	ZwlrDataControlDeviceV1,
+	ZwlrDataControlOfferV1,
+	ZwlrDataControlManagerV1
+);
+
+delegate_noop!(State: ignore wl_seat::WlSeat);
+
+fn failed(context: &str, e: impl std::fmt::Display) -> InsertError {
+	InsertError::Failed(format!("{context}: {e}"))
+}
+
+/// Makes `text` the clipboard selection. The selection is in place once this
+/// returns. A `transient` selection is marked so clipboard managers skip it.
+pub(crate) fn offer(text: &str, transient: bool) -> Result<ClipboardSource, InsertError> {
+	let connection =
+		Connection::connect_to_env().map_err(|e| failed("could not connect to Wayland", e))?;
+	let (globals, mut queue) = registry_queue_init::<State>(&connection)
+		.map_err(|e| failed("could not read Wayland globals", e))?;
+	let qh = queue.handle();
+
+	let seat: wl_seat::WlSeat =
+		globals.bind(&qh, 1..=1, ()).map_err(|e| failed("no Wayland seat", e))?;
+	let mime_types = TEXT_MIME_TYPES.iter().chain(transient.then_some(&CLIPBOARD_MANAGER_HINT));
+	if let Ok(manager) = globals.bind::<ExtDataControlManagerV1, _, _>(&qh, 1..=1, ()) {
+		let source = manager.create_data_source(&qh, ());
+		mime_types.for_each(|mime_type| source.offer(mime_type.to_string()));
+		manager.get_data_device(&seat, &qh, ()).set_selection(Some(&source));
+	} else {
+		let manager: ZwlrDataControlManagerV1 = globals
+			.bind(&qh, 1..=2, ())
+			.map_err(|e| InsertError::Unsupported(format!("clipboard control unsupported: {e}")))?;
+		let source = manager.create_data_source(&qh, ());
+		mime_types.for_each(|mime_type| source.offer(mime_type.to_string()));
+		manager.get_data_device(&seat, &qh, ()).set_selection(Some(&source));
+	}
+
+	let (requests_tx, requests) = mpsc::channel();
+	let mut state =
+		State { contents: text.as_bytes().to_vec(), requests: requests_tx, cancelled: false };
+	queue.roundtrip(&mut state).map_err(|e| failed("selection was not accepted", e))?;
+	if state.cancelled {
+		return Err(InsertError::Failed("selection was replaced straight away".into()));
+	}
+
+	thread::spawn(move || serve(queue, state));
+	Ok(ClipboardSource { requests })
+}
+
+fn serve(mut queue: EventQueue<State>, mut state: State) {
+	while !state.cancelled {
+		if let Err(e) = queue.blocking_dispatch(&mut state) {
+			debug!("stopped serving the clipboard: {e}");
+			return;
+		}
+	}
+	debug!("clipboard selection was replaced");
+}
@@ -0,0 +1,271 @@
+//! Output formats an emoji can be inserted as.
+
+use std::borrow::Cow;
+
+use emoji::Emoji;
+use serde::Deserialize;
+
+use super::variation_sequences::TEXT_DEFAULT_BASES;
+
+/// Variation selector asking for the monochrome text presentation (VS15).
+const TEXT_SELECTOR: char = '\u{FE0E}';
+/// Variation selector asking for the colourful emoji presentation (VS16).
+const EMOJI_SELECTOR: char = '\u{FE0F}';
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+	/// The emoji itself, e.g. `🔥`.
+	#[default]
+	Glyph,
+	/// The GitHub `:shortcode:`, e.g. `:fire:`; emoji without one, such as skin
+	/// tone variants, stay glyphs.
+	Shortcode,
+	/// Space-separated code points, e.g. `U+1F525`.
+	Codepoint,
+	/// Rust/JavaScript style escapes, e.g. `\u{1F525}`.
+	Escape,
+	/// Hexadecimal HTML character references, e.g. `&#x1F525;`.
+	HtmlEntity,
+	/// The CLDR short name, e.g. `fire`.
+	Name,
+}
+
+/// Which presentation ambiguous emoji such as `❤` or `✔` are inserted in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Presentation {
+	/// Keep the glyph as the emoji data has it.
+	#[default]
+	AsIs,
+	/// Always ask for the emoji presentation (U+FE0F).
+	Emoji,
+	/// Always ask for the text presentation (U+FE0E).
+	Text,
+}
+
+impl Presentation {
+	/// `glyph` with its variation selector set to this presentation; glyphs
+	/// without a text presentation come back unchanged.
+	pub(crate) fn apply(self, glyph: &str) -> Cow<'_, str> {
+		let selector = match self {
+			Self::AsIs => return Cow::Borrowed(glyph),
+			Self::Emoji => EMOJI_SELECTOR,
+			Self::Text => TEXT_SELECTOR,
+		};
+		if !is_ambiguous(glyph) {
+			return Cow::Borrowed(glyph);
+		}
+
+		let mut chars = glyph.chars();
+		let base = chars.next().unwrap_or_default();
+		let rest = chars.as_str().trim_start_matches([TEXT_SELECTOR, EMOJI_SELECTOR]);
+		Cow::Owned(format!("{base}{selector}{rest}"))
+	}
+}
+
+/// Whether a glyph is shown differently depending on its variation selector:
+/// a lone character that shows as text unless it is given one, as in `❤️` or
+/// the keycap `1️⃣`. The emoji data spells most of these with a selector, but
+/// not older symbols such as `☺`.
+pub(crate) fn is_ambiguous(glyph: &str) -> bool {
+	if glyph.contains('\u{200D}') {
+		return false;
+	}
+	let mut chars = glyph.chars();
+	match (chars.next(), chars.next()) {
+		(Some(base), None | Some(TEXT_SELECTOR | EMOJI_SELECTOR)) => {
+			TEXT_DEFAULT_BASES.binary_search(&base).is_ok()
+		}
+		_ => false,
+	}
+}
+
+/// The other form of an ambiguous glyph: the text presentation when it asks for
+/// the emoji one, and the emoji presentation otherwise.
+pub(crate) fn other_presentation(glyph: &str) -> Cow<'_, str> {
+	if glyph.contains(EMOJI_SELECTOR) {
+		Presentation::Text.apply(glyph)
+	} else {
+		Presentation::Emoji.apply(glyph)
+	}
+}
+
+impl OutputFormat {
+	/// `emoji` in this format, its glyph in the given `presentation`.
+	pub(crate) fn render(self, emoji: &Emoji, presentation: Presentation) -> String {
+		let glyph = &*presentation.apply(emoji.glyph);
+		match self {
+			Self::Glyph => glyph.to_string(),
+			Self::Shortcode => match shortcode(emoji.glyph) {
+				Some(code) => format!(":{code}:"),
+				None => glyph.to_string(),
+			},
+			Self::Codepoint => {
+				glyph.chars().map(|ch| format!("U+{:04X}", ch as u32)).collect::<Vec<_>>().join(" ")
+			}
+			Self::Escape => glyph.chars().map(|ch| format!("\\u{{{:X}}}", ch as u32)).collect(),
+			Self::HtmlEntity => glyph.chars().map(|ch| format!("&#x{:X};", ch as u32)).collect(),
+			Self::Name => emoji.name.to_string(),
+		}
+	}
+}
+
+/// Fills in an insertion template: `{glyph}`, `{name}` and `{shortcode}` stand
+/// for the emoji in those formats, so `"{glyph} "` adds a trailing space.
+pub(crate) fn render_template(template: &str, emoji: &Emoji, presentation: Presentation) -> String {
+	template
+		.replace("{glyph}", &OutputFormat::Glyph.render(emoji, presentation))
+		.replace("{name}", &OutputFormat::Name.render(emoji, presentation))
+		.replace("{shortcode}", &OutputFormat::Shortcode.render(emoji, presentation))
+}
+
+/// The GitHub shortcode of a glyph, from the gemoji table, without its colons.
+fn shortcode(glyph: &str) -> Option<&'static str> { emojis::get(glyph)?.shortcode() }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::lookup::by_glyph;
+
+	const FAMILY: &str = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+	const TECHNOLOGIST: &str = "\u{1F469}\u{1F3FD}\u{200D}\u{1F4BB}";
+	const GERMANY: &str = "\u{1F1E9}\u{1F1EA}";
+
+	fn lookup(glyph: &str) -> &'static Emoji {
+		by_glyph(glyph).unwrap_or_else(|| panic!("{glyph} is missing from the emoji data"))
+	}
+
+	#[test]
+	fn glyph_is_the_whole_sequence() {
+		for glyph in [FAMILY, TECHNOLOGIST, GERMANY] {
+			assert_eq!(OutputFormat::Glyph.render(lookup(glyph), Presentation::AsIs), glyph);
+		}
+	}
+
+	#[test]
+	fn name_is_the_cldr_name() {
+		assert_eq!(
+			OutputFormat::Name.render(lookup(FAMILY), Presentation::AsIs),
+			"family: man, woman, girl"
+		);
+		assert_eq!(
+			OutputFormat::Name.render(lookup(TECHNOLOGIST), Presentation::AsIs),
+			"woman technologist: medium skin tone"
+		);
+		assert_eq!(OutputFormat::Name.render(lookup(GERMANY), Presentation::AsIs), "flag: Germany");
+	}
+
+	#[test]
+	fn shortcode_is_the_github_one() {
+		assert_eq!(
+			OutputFormat::Shortcode.render(lookup(FAMILY), Presentation::AsIs),
+			":family_man_woman_girl:"
+		);
+		assert_eq!(OutputFormat::Shortcode.render(lookup(GERMANY), Presentation::AsIs), ":de:");
+		assert_eq!(shortcode("\u{1F600}"), Some("grinning"));
+		assert_eq!(shortcode("\u{1F44D}"), Some("+1"));
+	}
+
+	#[test]
+	fn glyphs_without_a_shortcode_stay_glyphs() {
+		assert_eq!(shortcode(TECHNOLOGIST), None);
+		assert_eq!(
+			OutputFormat::Shortcode.render(lookup(TECHNOLOGIST), Presentation::AsIs),
+			TECHNOLOGIST
+		);
+	}
+
+	#[test]
+	fn codepoint_lists_every_scalar() {
+		assert_eq!(
+			OutputFormat::Codepoint.render(lookup(FAMILY), Presentation::AsIs),
+			"U+1F468 U+200D U+1F469 U+200D U+1F467"
+		);
+		assert_eq!(
+			OutputFormat::Codepoint.render(lookup(TECHNOLOGIST), Presentation::AsIs),
+			"U+1F469 U+1F3FD U+200D U+1F4BB"
+		);
+		assert_eq!(
+			OutputFormat::Codepoint.render(lookup(GERMANY), Presentation::AsIs),
+			"U+1F1E9 U+1F1EA"
+		);
+	}
+
+	#[test]
+	fn escape_covers_the_joiners() {
+		assert_eq!(
+			OutputFormat::Escape.render(lookup(FAMILY), Presentation::AsIs),
+			"\\u{1F468}\\u{200D}\\u{1F469}\\u{200D}\\u{1F467}"
+		);
+		assert_eq!(
+			OutputFormat::Escape.render(lookup(TECHNOLOGIST), Presentation::AsIs),
+			"\\u{1F469}\\u{1F3FD}\\u{200D}\\u{1F4BB}"
+		);
+		assert_eq!(
+			OutputFormat::Escape.render(lookup(GERMANY), Presentation::AsIs),
+			"\\u{1F1E9}\\u{1F1EA}"
+		);
+	}
+
+	#[test]
+	fn html_entity_covers_the_joiners() {
+		assert_eq!(
+			OutputFormat::HtmlEntity.render(lookup(FAMILY), Presentation::AsIs),
+			"&#x1F468;&#x200D;&#x1F469;&#x200D;&#x1F467;"
+		);
+		assert_eq!(
+			OutputFormat::HtmlEntity.render(lookup(GERMANY), Presentation::AsIs),
+			"&#x1F1E9;&#x1F1EA;"
+		);
+	}
+
+	#[test]
+	fn templates_substitute_every_placeholder() {
+		let family = lookup(FAMILY);
+		assert_eq!(render_template("{glyph} ", family, Presentation::AsIs), format!("{FAMILY} "));
+		assert_eq!(
+			render_template("{name}: {glyph}{glyph}", family, Presentation::AsIs),
+			format!("family: man, woman, girl: {FAMILY}{FAMILY}")
+		);
+		assert_eq!(render_template("", family, Presentation::AsIs), "");
+	}
+
+	#[test]
+	fn templates_leave_unknown_placeholders_alone() {
+		assert_eq!(
+			render_template("{emoji} {Glyph} {glyph} {", lookup(GERMANY), Presentation::AsIs),
+			format!("{{emoji}} {{Glyph}} {GERMANY} {{")
+		);
+	}
+
+	#[test]
+	fn only_characters_shown_as_text_by_default_are_ambiguous() {
+		for glyph in ["\u{2764}\u{FE0F}", "\u{2714}\u{FE0F}", "\u{263A}", "\u{31}\u{FE0F}\u{20E3}"] {
+			assert!(is_ambiguous(glyph), "{glyph} is ambiguous");
+		}
+		for glyph in
+			["\u{2705}", "\u{274C}", "\u{231A}", "\u{1F525}", "\u{2764}\u{FE0F}\u{200D}\u{1F525}"]
+		{
+			assert!(!is_ambiguous(glyph), "{glyph} is not ambiguous");
+		}
+		assert!(TEXT_DEFAULT_BASES.is_sorted());
+	}
+
+	#[test]
+	fn presentation_sets_the_selector_of_ambiguous_glyphs() {
+		assert_eq!(Presentation::Text.apply("\u{2764}\u{FE0F}"), "\u{2764}\u{FE0E}");
+		assert_eq!(Presentation::Emoji.apply("\u{263A}"), "\u{263A}\u{FE0F}");
+		assert_eq!(Presentation::Text.apply("\u{31}\u{FE0F}\u{20E3}"), "\u{31}\u{FE0E}\u{20E3}");
+		assert_eq!(Presentation::AsIs.apply("\u{2764}\u{FE0F}"), "\u{2764}\u{FE0F}");
+		assert_eq!(Presentation::Text.apply("\u{2705}"), "\u{2705}");
+		assert_eq!(Presentation::Text.apply("\u{274C}"), "\u{274C}");
+	}
+
+	#[test]
+	fn other_presentation_flips_the_selector() {
+		assert_eq!(other_presentation("\u{2764}\u{FE0F}"), "\u{2764}\u{FE0E}");
+		assert_eq!(other_presentation("\u{2764}\u{FE0E}"), "\u{2764}\u{FE0F}");
+		assert_eq!(other_presentation("\u{263A}"), "\u{263A}\u{FE0F}");
+	}
+}
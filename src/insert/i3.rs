@@ -0,0 +1,63 @@
+//! Targeted insertion for sway and i3: the captured container gets focus back
+//! over IPC before anything is sent to it.
+
+use super::{
+	FOCUS_TIMEOUT,
+	automated::insert_enigo,
+	backend::{BackendKind, InsertBackend, InsertError, InsertTarget, Outcome},
+	rules, virtual_keyboard,
+	wayland::paste_via_clipboard,
+	x11_paste::paste_x11,
+};
+use crate::{
+	config::CONFIG,
+	integration::{
+		i3ipc,
+		linux::{LinuxSession, detect_linux_session, wayland_capabilities},
+	},
+};
+
+/// Refocuses the captured container, then pastes with the shortcut its
+/// `app_id`/class rule picks on sway. i3 runs on X11, where the text is typed
+/// instead unless the rule prefers pasting.
+pub(crate) struct I3PasteBackend;
+
+impl InsertBackend for I3PasteBackend {
+	fn kind(&self) -> BackendKind { BackendKind::I3Paste }
+
+	fn available(&self) -> bool {
+		i3ipc::socket_path().is_some()
+			&& match detect_linux_session() {
+				LinuxSession::WaylandSway => wayland_capabilities().virtual_keyboard,
+				LinuxSession::X11 => true,
+				_ => false,
+			}
+	}
+
+	fn insert(&self, text: &str, target: &InsertTarget) -> Result<Outcome, InsertError> {
+		let Some(con_id) = target.i3_con_id else {
+			return Err(InsertError::Unsupported("no sway/i3 container was captured".into()));
+		};
+
+		i3ipc::focus_container(con_id)
+			.map_err(|e| InsertError::Failed(format!("could not refocus container {con_id}: {e}")))?;
+		let focused = i3ipc::wait_for_focus(con_id, FOCUS_TIMEOUT)
+			.map_err(|e| InsertError::Failed(format!("could not follow sway/i3 focus: {e}")))?;
+		if !focused {
+			return Err(InsertError::Failed(format!("container {con_id} did not take focus")));
+		}
+
+		let rule = rules::resolve(&CONFIG.rules, target.class.as_deref(), target.title.as_deref());
+		if detect_linux_session() == LinuxSession::X11 {
+			if rule.prefer_paste {
+				paste_x11(text, rule.paste)?;
+			} else {
+				insert_enigo(text)?;
+			}
+			return Ok(Outcome::Inserted);
+		}
+
+		paste_via_clipboard(text, || virtual_keyboard::press_shortcut(rule.paste))?;
+		Ok(Outcome::Inserted)
+	}
+}
@@ -0,0 +1,126 @@
+//! Insertion through the Wayland `zwp_input_method_v2` protocol, committing the
+//! text to the focused text field the way an IME would.
+
+use std::time::{Duration, Instant};
+
+use wayland_client::{
+	Connection, Dispatch, QueueHandle, delegate_noop,
+	globals::{GlobalListContents, registry_queue_init},
+	protocol::{wl_registry, wl_seat},
+};
+use wayland_protocols_misc::zwp_input_method_v2::client::{
+	zwp_input_method_manager_v2::ZwpInputMethodManagerV2,
+	zwp_input_method_v2::{self, ZwpInputMethodV2},
+};
+
+use super::backend::{BackendKind, InsertBackend, InsertError, InsertTarget, Outcome};
+use crate::integration::linux::{dispatch_within, wayland_capabilities};
+
+/// How long to wait for a text field to gain focus once the picker is gone.
+/// Applications without text-input support never activate the input method,
+/// so this bounds how long they delay the next backend.
+const ACTIVATION_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Commits text as an input method. It needs no keymap and leaves the
+/// clipboard alone, but only reaches applications speaking text-input-v3.
+pub(crate) struct InputMethodBackend;
+
+impl InsertBackend for InputMethodBackend {
+	fn kind(&self) -> BackendKind { BackendKind::InputMethod }
+
+	fn available(&self) -> bool { wayland_capabilities().input_method }
+
+	fn insert(&self, text: &str, _target: &InsertTarget) -> Result<Outcome, InsertError> {
+		commit_text(text)?;
+		Ok(Outcome::Inserted)
+	}
+}
+
+#[derive(Default)]
+struct State {
+	active:      bool,
+	/// Number of `done` events seen, which is the serial `commit` expects.
+	done_count:  u32,
+	unavailable: bool,
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+	fn event(
+		_: &mut Self,
+		_: &wl_registry::WlRegistry,
+		_: wl_registry::Event,
+		_: &GlobalListContents,
+		_: &Connection,
+		_: &QueueHandle<Self>,
+	) {
+	}
+}
+
+impl Dispatch<ZwpInputMethodV2, ()> for State {
+	fn event(
+		state: &mut Self,
+		_: &ZwpInputMethodV2,
+		event: zwp_input_method_v2::Event,
+		_: &(),
+		_: &Connection,
+		_: &QueueHandle<Self>,
+	) {
+		match event {
+			zwp_input_method_v2::Event::Activate => state.active = true,
+			zwp_input_method_v2::Event::Deactivate => state.active = false,
+			zwp_input_method_v2::Event::Done => state.done_count += 1,
+			zwp_input_method_v2::Event::Unavailable => state.unavailable = true,
+			_ => {}
+		}
+	}
+}
+
+delegate_noop!(State: ignore wl_seat::WlSeat);
+delegate_noop!(State: ZwpInputMethodManagerV2);
+
+fn failed(context: &str, e: impl std::fmt::Display) -> InsertError {
+	InsertError::Failed(format!("{context}: {e}"))
+}
+
+fn commit_text(text: &str) -> Result<(), InsertError> {
+	let connection =
+		Connection::connect_to_env().map_err(|e| failed("could not connect to Wayland", e))?;
+	let (globals, mut queue) = registry_queue_init::<State>(&connection)
+		.map_err(|e| failed("could not read Wayland globals", e))?;
+	let qh = queue.handle();
+
+	let seat: wl_seat::WlSeat =
+		globals.bind(&qh, 1..=1, ()).map_err(|e| failed("no Wayland seat", e))?;
+	let manager: ZwpInputMethodManagerV2 =
+		globals.bind(&qh, 1..=1, ()).map_err(|e| failed("input method unsupported", e))?;
+	let input_method = manager.get_input_method(&seat, &qh, ());
+
+	let mut state = State::default();
+	let deadline = Instant::now() + ACTIVATION_TIMEOUT;
+	queue.roundtrip(&mut state).map_err(|e| failed("input method roundtrip failed", e))?;
+	loop {
+		if state.unavailable {
+			input_method.destroy();
+			return Err(InsertError::Unsupported("another input method is already running".into()));
+		}
+		// Activation only takes effect with the `done` that follows it.
+		if state.active && state.done_count > 0 {
+			break;
+		}
+		let left = deadline.saturating_duration_since(Instant::now());
+		if left.is_zero() {
+			input_method.destroy();
+			return Err(InsertError::Unsupported("no text field took input method focus".into()));
+		}
+
+		dispatch_within(&mut queue, &mut state, left)
+			.map_err(|e| failed("lost the input method events", e))?;
+	}
+
+	input_method.commit_string(text.to_string());
+	input_method.commit(state.done_count);
+	queue.roundtrip(&mut state).map_err(|e| failed("commit was not delivered", e))?;
+	input_method.destroy();
+	connection.flush().map_err(|e| failed("could not flush Wayland connection", e))?;
+	Ok(())
+}
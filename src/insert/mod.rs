@@ -1,85 +1,403 @@
 //! Cross-platform emoji insertion dispatcher.
 
 mod automated;
+pub(crate) mod backend;
+pub(crate) mod format;
+pub(crate) mod notify;
+pub(crate) mod rules;
+pub(crate) mod selection;
+mod variation_sequences;
+
+#[cfg(target_os = "linux")]
+mod clipboard_source;
+
+#[cfg(target_os = "linux")]
+mod i3;
+
+#[cfg(target_os = "linux")]
+mod input_method;
+
+#[cfg(target_os = "linux")]
+mod niri;
+
+#[cfg(target_os = "linux")]
+mod portal;
+
+#[cfg(target_os = "linux")]
+mod uinput;
 
 #[cfg(target_os = "linux")]
 mod unassisted;
 
 #[cfg(target_os = "linux")]
-mod wayland;
+pub(crate) mod undo;
 
-use std::{thread, time::Duration};
+#[cfg(target_os = "linux")]
+mod virtual_keyboard;
+
+#[cfg(target_os = "linux")]
+pub(crate) mod wayland;
 
-use tracing::debug;
 #[cfg(target_os = "linux")]
-use tracing::error;
+mod x11_paste;
 
+use std::{sync::mpsc, thread, time::Duration};
 
-static INSERT_DELAY: Duration = Duration::from_millis(75);
+use backend::{BackendKind, InsertBackend, InsertError, InsertTarget};
+use emoji::Emoji;
+use nonempty::NonEmpty;
+use tracing::{debug, error};
 
-/// How long to keep the process alive after the window closes so the
-/// background insertion thread can finish its work.
+use crate::{components::types::SelectedEmoji, config::CONFIG};
+
+/// How long the insertion waits for the picker window to go away before it
+/// starts anyway.
+static CLOSE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long the target window gets to take focus back.
+static FOCUS_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long the keys of a global shortcut get to be let go of.
+#[cfg(target_os = "linux")]
+static MODIFIER_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// How often focus is looked at again on macOS, which has no notification the
+/// wait could block on.
+#[cfg(target_os = "macos")]
+static HANDSHAKE_POLL: Duration = Duration::from_millis(5);
+
+/// Upper bound on how long the process stays alive after the window closes
+/// while the background insertion finishes.
 #[cfg(not(feature = "service"))]
-static LINGER_AFTER_CLOSE: Duration = Duration::from_millis(150);
+static QUIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether the picker reopens after inserting instead of going away.
+#[derive(Default)]
+pub(crate) struct KeepOpen(pub bool);
+impl gpui::Global for KeepOpen {}
+
+/// Count typed before picking, which inserts the pick that many times.
+#[derive(Default)]
+pub(crate) struct RepeatCount(pub Option<u32>);
+impl gpui::Global for RepeatCount {}
 
-fn insert_emoji(emoji: &str, cx: &gpui::App) {
+/// Largest count accepted, so a stray run of digits cannot flood the target.
+const MAX_REPEAT: u32 = 100;
+
+impl RepeatCount {
+	/// Appends a typed digit to the count.
+	pub(crate) fn push_digit(&mut self, digit: u32) {
+		self.0 = Some((self.0.unwrap_or(0) * 10 + digit).min(MAX_REPEAT));
+	}
+}
+
+/// Polls `check` until it holds or `timeout` runs out; `false` on timeout.
+#[cfg(target_os = "macos")]
+fn wait_until(timeout: Duration, mut check: impl FnMut() -> bool) -> bool {
+	use std::time::Instant;
+
+	let deadline = Instant::now() + timeout;
+	loop {
+		if check() {
+			return true;
+		}
+		if Instant::now() >= deadline {
+			return false;
+		}
+		thread::sleep(HANDSHAKE_POLL);
+	}
+}
+
+/// Instantiates the configured backends in priority order.
+fn backend_chain() -> Vec<Box<dyn InsertBackend>> {
+	let kinds =
+		if CONFIG.backends.is_empty() { BackendKind::DEFAULT_CHAIN } else { &CONFIG.backends[..] };
+
+	kinds
+		.iter()
+		.filter_map(|kind| -> Option<Box<dyn InsertBackend>> {
+			match kind {
+				BackendKind::Enigo => Some(Box::new(automated::EnigoBackend)),
+				#[cfg(target_os = "linux")]
+				BackendKind::InputMethod => Some(Box::new(input_method::InputMethodBackend)),
+				#[cfg(target_os = "linux")]
+				BackendKind::HyprlandPaste => Some(Box::new(wayland::HyprlandPasteBackend)),
+				#[cfg(target_os = "linux")]
+				BackendKind::I3Paste => Some(Box::new(i3::I3PasteBackend)),
+				#[cfg(target_os = "linux")]
+				BackendKind::NiriType => Some(Box::new(niri::NiriTypeBackend)),
+				#[cfg(target_os = "linux")]
+				BackendKind::VirtualKeyboard => Some(Box::new(virtual_keyboard::VirtualKeyboardBackend)),
+				#[cfg(target_os = "linux")]
+				BackendKind::PortalPaste => Some(Box::new(portal::PortalPasteBackend)),
+				#[cfg(target_os = "linux")]
+				BackendKind::UinputPaste => Some(Box::new(uinput::UinputPasteBackend)),
+				#[cfg(target_os = "linux")]
+				BackendKind::X11Paste => Some(Box::new(x11_paste::X11PasteBackend)),
+				#[cfg(target_os = "linux")]
+				BackendKind::Clipboard => Some(Box::new(unassisted::ClipboardBackend)),
+				#[allow(unreachable_patterns)]
+				other => {
+					debug!(%other, "insertion backend not supported on this platform");
+					None
+				}
+			}
+		})
+		.collect()
+}
+
+/// Starts the background insertion, which holds off until `closed` says the
+/// picker is gone; the returned receiver resolves once it is done.
+fn insert_emoji(
+	emoji: &str,
+	picked: Vec<Emoji>,
+	closed: mpsc::Receiver<()>,
+	cx: &gpui::App,
+) -> mpsc::Receiver<()> {
 	let emoji_owned = emoji.to_string();
 	debug!(emoji = %emoji, "inserting emoji");
 
-	#[cfg(target_os = "linux")]
-	let target = cx.try_global::<crate::integration::linux::PendingInsertTarget>().cloned();
+	let target = cx.try_global::<InsertTarget>().cloned().unwrap_or_default();
 
-	#[cfg(not(target_os = "linux"))]
-	let _ = cx;
+	let (done_tx, done_rx) = mpsc::channel();
 
 	thread::spawn(move || {
-		#[cfg(target_os = "macos")]
-		{
-			thread::sleep(INSERT_DELAY);
-			automated::insert_enigo(&emoji_owned);
-			return;
+		if closed.recv_timeout(CLOSE_TIMEOUT).is_err() {
+			debug!("picker did not confirm closing; inserting anyway");
 		}
 
-		#[cfg(target_os = "linux")]
-		{
-			use crate::integration::linux::{LinuxSession, detect_linux_session};
+		let _ = run_insertion(&emoji_owned, &picked, &target);
+		let _ = done_tx.send(());
+	});
 
-			match detect_linux_session() {
-				LinuxSession::X11 => {
-					thread::sleep(INSERT_DELAY);
-					automated::insert_enigo(&emoji_owned);
-				}
-				LinuxSession::WaylandHyprland => {
-					wayland::insert_hyprland(&emoji_owned, target.as_ref());
-				}
-				LinuxSession::WaylandOther => {
-					unassisted::copy_to_clipboard_wayland(&emoji_owned);
-				}
-				LinuxSession::Unknown => {
-					error!("could not detect display session; emoji not inserted");
+	done_rx
+}
+
+/// Sends `text`, rendered from the `picked` emoji, down the backend chain and
+/// does the bookkeeping around it: history, undo, the primary selection and
+/// notifications.
+fn run_insertion(
+	text: &str,
+	picked: &[Emoji],
+	target: &InsertTarget,
+) -> Result<backend::Outcome, InsertError> {
+	let result = backend::insert_with_fallback(&backend_chain(), text, target);
+	match &result {
+		Ok((kind, outcome)) => {
+			debug!(%kind, ?outcome, "emoji insertion finished");
+			crate::history::record(picked);
+
+			#[cfg(target_os = "linux")]
+			if *outcome == backend::Outcome::Inserted {
+				undo::remember(text, target);
+
+				// Copy fallbacks deal with PRIMARY themselves.
+				if CONFIG.primary_selection != selection::PrimarySelection::Off
+					&& let Err(e) = selection::set_primary(text)
+				{
+					error!("{e}");
 				}
+			} else {
+				// The text never reached a window, so undo has nothing to take back.
+				undo::forget();
 			}
 		}
-	});
+		Err(e) => {
+			error!("emoji not inserted: {e}");
+			#[cfg(target_os = "linux")]
+			undo::forget();
+		}
+	}
+
+	let result = result.map(|(_, outcome)| outcome);
+	notify::notify_outcome(CONFIG.notify, text, &result);
+	result
+}
+
+/// Inserts the `n`-th most recent pick again, counting from 1, into whatever
+/// has focus; no picker is shown. The pick is rendered for the window it now
+/// goes to, once, whatever count or format it was first inserted with.
+pub(crate) fn reinsert(n: usize) -> Result<backend::Outcome, InsertError> {
+	let Some(picked) = crate::history::nth(n) else {
+		let result =
+			Err(InsertError::Unsupported(format!("there is no insertion number {n} to repeat")));
+		notify::notify_outcome(CONFIG.notify, "a recent emoji", &result);
+		return result;
+	};
+	debug!(n, count = picked.len(), "re-inserting");
+
+	let target = capture_target();
+	let rule = rule_for(&target);
+	let text: String = picked.iter().map(|emoji| render(emoji, &rule)).collect();
+	let picked: Vec<Emoji> = picked.into_iter().cloned().collect();
+	run_insertion(&text, &picked, &target)
+}
+
+/// Inserts `text` into whatever has focus, straight from a hotkey or the
+/// command line; no picker is shown. Text that is a single known emoji goes
+/// into the history like a pick.
+pub(crate) fn insert_now(text: &str) -> Result<backend::Outcome, InsertError> {
+	let picked: Vec<Emoji> = crate::lookup::by_glyph(text).into_iter().cloned().collect();
+	run_insertion(text, &picked, &capture_target())
 }
 
-/// Insert effectively
-pub(crate) fn close_and_insert(emoji: &str, cx: &mut gpui::App) {
+/// Records the window that has focus for an insertion without a picker.
+#[cfg(target_os = "linux")]
+fn capture_target() -> InsertTarget {
+	wait_for_shortcut_release();
+	crate::integration::linux::capture_focused_window()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn capture_target() -> InsertTarget { InsertTarget::default() }
+
+/// Waits for the keys of the shortcut that triggered an action to be let go,
+/// so they do not combine with the keys sent. Only X11 can tell.
+#[cfg(target_os = "linux")]
+fn wait_for_shortcut_release() {
+	use tracing::warn;
+
+	use crate::integration::{
+		linux::{LinuxSession, detect_linux_session},
+		x11,
+	};
+
+	if detect_linux_session() != LinuxSession::X11 {
+		return;
+	}
+	match x11::wait_for_modifiers_released(MODIFIER_TIMEOUT) {
+		Ok(true) => {}
+		Ok(false) => warn!("modifiers are still held; sending keys anyway"),
+		Err(e) => warn!("could not read the modifier state: {e}"),
+	}
+}
+
+/// Resolves the application rules for the window the emoji is headed to.
+fn target_rule(cx: &gpui::App) -> rules::ResolvedRule {
+	#[cfg(target_os = "linux")]
+	{
+		if let Some(target) = cx.try_global::<crate::integration::linux::PendingInsertTarget>() {
+			return rules::resolve(&CONFIG.rules, target.class.as_deref(), target.title.as_deref());
+		}
+	}
+
+	#[cfg(not(target_os = "linux"))]
+	let _ = cx;
+
+	rules::resolve(&CONFIG.rules, None, None)
+}
+
+/// Resolves the application rules for a target captured without a picker.
+fn rule_for(target: &InsertTarget) -> rules::ResolvedRule {
+	#[cfg(target_os = "linux")]
+	let (class, title) = (target.class.as_deref(), target.title.as_deref());
+	#[cfg(not(target_os = "linux"))]
+	let (class, title) = {
+		let _ = target;
+		(None, None)
+	};
+
+	rules::resolve(&CONFIG.rules, class, title)
+}
+
+/// Renders an emoji through the configured template, or else in the format
+/// the target application's rule asks for, in the rule's presentation if it
+/// names one.
+fn render(emoji: &Emoji, rule: &rules::ResolvedRule) -> String {
+	let presentation = rule.presentation.unwrap_or(CONFIG.presentation);
+	match &CONFIG.template {
+		Some(template) => format::render_template(template, emoji, presentation),
+		None => rule.format.unwrap_or(CONFIG.output_format).render(emoji, presentation),
+	}
+}
+
+/// Insert effectively, rendered for the target application
+pub(crate) fn close_and_insert(emoji: &Emoji, cx: &mut gpui::App) {
+	let text = render(emoji, &target_rule(cx));
+	close_and_insert_text(&text, NonEmpty::new(emoji.clone()), cx);
+}
+
+/// Insert in an output format picked at selection time, which stands in for
+/// the target application's format; its other rules and the template still
+/// apply
+pub(crate) fn close_and_insert_as(emoji: &Emoji, format: format::OutputFormat, cx: &mut gpui::App) {
+	let rule = rules::ResolvedRule { format: Some(format), ..target_rule(cx) };
+	let text = render(emoji, &rule);
+	close_and_insert_text(&text, NonEmpty::new(emoji.clone()), cx);
+}
+
+/// Insert a staged sequence in one go
+pub(crate) fn close_and_insert_all(emojis: NonEmpty<Emoji>, cx: &mut gpui::App) {
+	let rule = target_rule(cx);
+	let text: String = emojis.iter().map(|emoji| render(emoji, &rule)).collect();
+	close_and_insert_text(&text, emojis, cx);
+}
+
+/// Insert the emoji and bring the picker back afterwards, whatever the
+/// keep-open toggle says
+pub(crate) fn insert_and_reopen(emoji: &Emoji, cx: &mut gpui::App) {
+	let text = render(emoji, &target_rule(cx));
+	finish_insert(&text, NonEmpty::new(emoji.clone()), true, cx);
+}
+
+fn close_and_insert_text(text: &str, picked: NonEmpty<Emoji>, cx: &mut gpui::App) {
+	let keep_open = cx.try_global::<KeepOpen>().is_some_and(|keep_open| keep_open.0);
+	finish_insert(text, picked, keep_open, cx);
+}
+
+/// Inserts `text`, rendered from the `picked` emoji, which become the
+/// `SelectedEmoji` and, once inserted, the newest history entry.
+fn finish_insert(text: &str, picked: NonEmpty<Emoji>, keep_open: bool, cx: &mut gpui::App) {
+	// A count typed before picking repeats the whole insertion.
+	let count = cx.try_global::<RepeatCount>().and_then(|count| count.0).filter(|&n| n > 0);
+	cx.set_global(RepeatCount::default());
+	let text = &text.repeat(count.unwrap_or(1) as usize);
+
 	// Start the background insertion (types into the now-focused app).
-	insert_emoji(emoji, cx);
+	let (closed_tx, closed) = mpsc::channel();
+	let done = insert_emoji(text, picked.iter().cloned().collect(), closed, cx);
+	cx.set_global(SelectedEmoji(Some(picked)));
 
-	// Begin closedown sequence
+	// Begin closedown sequence; closing the window hands focus back to the
+	// target either way.
 	cx.shutdown();
 
+	// Tasks run once the event loop is back, by which point the window is gone.
+	cx.spawn(|_: &mut gpui::AsyncApp| async move {
+		let _ = closed_tx.send(());
+	})
+	.detach();
+
+	// In keep-open mode, reopen the picker once the insertion has landed so
+	// the next pick goes to the same place.
+	if keep_open {
+		cx.spawn(|ctx: &mut gpui::AsyncApp| {
+			let ctx = ctx.clone();
+			async move {
+				// The insertion runs on its own thread, so blocking on it here only
+				// holds a background thread.
+				let _ = ctx.background_executor().spawn(async move { done.recv() }).await;
+
+				let _ = ctx.update(crate::lifecycle::open_picker);
+			}
+		})
+		.detach();
+		return;
+	}
+
 	// In service mode the app keeps running, so nothing else to do.
 	// In non-service mode we need to quit eventually, but only after
-	// the insertion thread has had time to finish.
+	// the insertion thread has finished.
 	#[cfg(not(feature = "service"))]
 	{
 		cx.spawn(|ctx: &mut gpui::AsyncApp| {
 			let ctx = ctx.clone();
 			async move {
-				ctx.background_executor().timer(LINGER_AFTER_CLOSE).await;
+				let finished =
+					ctx.background_executor().spawn(async move { done.recv_timeout(QUIT_TIMEOUT) }).await;
+				if let Err(mpsc::RecvTimeoutError::Timeout) = finished {
+					error!("insertion is still running; quitting anyway");
+				}
 
 				let _ = ctx.update(|cx| cx.quit());
 			}
@@ -87,3 +405,33 @@ pub(crate) fn close_and_insert(emoji: &str, cx: &mut gpui::App) {
 		.detach();
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn typed_digits_accumulate() {
+		let mut count = RepeatCount::default();
+		count.push_digit(1);
+		assert_eq!(count.0, Some(1));
+		count.push_digit(2);
+		assert_eq!(count.0, Some(12));
+		count.push_digit(0);
+		assert_eq!(count.0, Some(100));
+	}
+
+	#[test]
+	fn counts_stop_at_the_cap() {
+		let mut count = RepeatCount::default();
+		for digit in [9, 9] {
+			count.push_digit(digit);
+		}
+		assert_eq!(count.0, Some(99));
+
+		count.push_digit(9);
+		assert_eq!(count.0, Some(MAX_REPEAT));
+		count.push_digit(5);
+		assert_eq!(count.0, Some(MAX_REPEAT));
+	}
+}
@@ -0,0 +1,41 @@
+//! Targeted insertion for niri: the captured window gets focus back over IPC
+//! and the text is typed on a virtual keyboard.
+
+use super::{
+	FOCUS_TIMEOUT,
+	backend::{BackendKind, InsertBackend, InsertError, InsertTarget, Outcome},
+	virtual_keyboard,
+};
+use crate::integration::{
+	linux::{LinuxSession, detect_linux_session, wayland_capabilities},
+	niri,
+};
+
+/// Refocuses the captured window before typing, so a window that opened in
+/// the meantime does not receive the text.
+pub(crate) struct NiriTypeBackend;
+
+impl InsertBackend for NiriTypeBackend {
+	fn kind(&self) -> BackendKind { BackendKind::NiriType }
+
+	fn available(&self) -> bool {
+		detect_linux_session() == LinuxSession::WaylandNiri && wayland_capabilities().virtual_keyboard
+	}
+
+	fn insert(&self, text: &str, target: &InsertTarget) -> Result<Outcome, InsertError> {
+		let Some(id) = target.niri_window_id else {
+			return Err(InsertError::Unsupported("no niri window was captured".into()));
+		};
+
+		niri::focus_window(id)
+			.map_err(|e| InsertError::Failed(format!("could not refocus niri window {id}: {e}")))?;
+		let focused = niri::wait_for_focus(id, FOCUS_TIMEOUT)
+			.map_err(|e| InsertError::Failed(format!("could not follow niri focus: {e}")))?;
+		if !focused {
+			return Err(InsertError::Failed(format!("niri window {id} did not take focus")));
+		}
+
+		virtual_keyboard::type_text(text)?;
+		Ok(Outcome::Inserted)
+	}
+}
@@ -0,0 +1,113 @@
+//! Desktop notifications for insertions that did not reach the target window.
+
+use serde::Deserialize;
+
+use super::backend::{InsertError, Outcome};
+
+/// Which insertion outcomes raise a desktop notification.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Notify {
+	/// Stay quiet; outcomes only go to the log.
+	Never,
+	/// Only when nothing could be inserted at all.
+	Failures,
+	/// Whenever the text did not land in the window, including when it was
+	/// only copied for the user to paste.
+	#[default]
+	Fallbacks,
+}
+
+/// Tells the user what became of `text` when it did not simply land where it
+/// was headed.
+#[cfg(target_os = "linux")]
+pub(crate) fn notify_outcome(level: Notify, text: &str, result: &Result<Outcome, InsertError>) {
+	use crate::integration::notifications::notify;
+
+	match result {
+		Ok(Outcome::Copied) if level == Notify::Fallbacks => {
+			notify(&format!("Copied {text}"), "It could not be typed into the window; paste it instead.");
+		}
+		Err(e) if level != Notify::Never => notify(&format!("Could not insert {text}"), &e.to_string()),
+		_ => {}
+	}
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn notify_outcome(_level: Notify, _text: &str, _result: &Result<Outcome, InsertError>) {}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+	use std::{
+		collections::HashMap,
+		sync::{Arc, Mutex},
+	};
+
+	use zbus::zvariant::OwnedValue;
+
+	use super::*;
+	use crate::test_support::{PrivateBus, ScopedEnv, lock_env};
+
+	/// Stands in for a notification daemon, recording the summary and body of
+	/// every notice.
+	struct MockNotifications {
+		shown: Arc<Mutex<Vec<(String, String)>>>,
+	}
+
+	#[zbus::interface(name = "org.freedesktop.Notifications")]
+	impl MockNotifications {
+		#[allow(clippy::too_many_arguments)]
+		fn notify(
+			&self,
+			_app_name: String,
+			_replaces_id: u32,
+			_app_icon: String,
+			summary: String,
+			body: String,
+			_actions: Vec<String>,
+			_hints: HashMap<String, OwnedValue>,
+			_expire_timeout: i32,
+		) -> u32 {
+			let mut shown = self.shown.lock().unwrap();
+			shown.push((summary, body));
+			shown.len() as u32
+		}
+	}
+
+	#[test]
+	fn notices_follow_the_notify_setting() {
+		let _env = lock_env();
+		let Some(bus) = PrivateBus::start() else {
+			return;
+		};
+		let mut env = ScopedEnv::default();
+		env.set("DBUS_SESSION_BUS_ADDRESS", &bus.address);
+
+		let shown = Arc::new(Mutex::new(Vec::new()));
+		let daemon = MockNotifications { shown: shown.clone() };
+		let _server = zbus::blocking::connection::Builder::address(bus.address.as_str())
+			.and_then(|builder| builder.name("org.freedesktop.Notifications"))
+			.and_then(|builder| builder.serve_at("/org/freedesktop/Notifications", daemon))
+			.and_then(|builder| builder.build())
+			.expect("stand-in notification daemon starts");
+
+		let copied = ("Copied 🦀", "It could not be typed into the window; paste it instead.");
+		let failed = ("Could not insert 🦀", "no insertion backend succeeded");
+		let cases = [
+			(Notify::Never, vec![]),
+			(Notify::Failures, vec![failed]),
+			(Notify::Fallbacks, vec![copied, failed]),
+		];
+
+		for (level, expected) in cases {
+			shown.lock().unwrap().clear();
+			notify_outcome(level, "🦀", &Ok(Outcome::Inserted));
+			notify_outcome(level, "🦀", &Ok(Outcome::Copied));
+			notify_outcome(level, "🦀", &Err(InsertError::Exhausted));
+			let shown = shown.lock().unwrap();
+			let shown: Vec<_> =
+				shown.iter().map(|(summary, body)| (summary.as_str(), body.as_str())).collect();
+			assert_eq!(shown, expected, "with {level:?}");
+		}
+	}
+}
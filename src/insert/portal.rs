@@ -0,0 +1,446 @@
+//! Paste insertion through the `org.freedesktop.portal.RemoteDesktop` portal,
+//! for sandboxed and portal-only desktops where no input protocol or
+//! `/dev/uinput` is reachable.
+
+use std::{fs, fs::File, io::Write, os::fd::OwnedFd, path::PathBuf, pin::pin};
+
+use ashpd::desktop::{
+	PersistMode, Session,
+	clipboard::Clipboard,
+	remote_desktop::{DeviceType, KeyState, RemoteDesktop},
+};
+use async_io::Timer;
+use futures_lite::{FutureExt, StreamExt};
+use tracing::{debug, warn};
+
+use super::{
+	backend::{BackendKind, InsertBackend, InsertError, InsertTarget, Outcome},
+	clipboard_source::TEXT_MIME_TYPES,
+	rules::{self, PasteShortcut},
+	uinput::shortcut_keys,
+	wayland::{PASTE_TIMEOUT, paste_via_clipboard},
+};
+use crate::{
+	config::{CONFIG, state_dir},
+	integration::linux::{LinuxSession, detect_linux_session, wayland_capabilities},
+};
+
+const RESTORE_TOKEN_FILE: &str = "remote-desktop-token";
+
+/// Copies the text and has the portal press the paste shortcut. The first use
+/// asks the user for consent; the restore token it hands back is kept so later
+/// sessions start without asking again.
+///
+/// Where the compositor does not let clients own the clipboard, as on GNOME,
+/// the text is offered through the portal's clipboard instead. The previous
+/// clipboard cannot be read there, so it is not restored.
+pub(crate) struct PortalPasteBackend;
+
+impl InsertBackend for PortalPasteBackend {
+	fn kind(&self) -> BackendKind { BackendKind::PortalPaste }
+
+	fn available(&self) -> bool { detect_linux_session() == LinuxSession::WaylandOther }
+
+	fn insert(&self, text: &str, target: &InsertTarget) -> Result<Outcome, InsertError> {
+		// Start the session before touching the clipboard so a refused consent
+		// dialog leaves the clipboard as it was.
+		let data_control = wayland_capabilities().data_control;
+		let keyboard = pollster::block_on(PortalKeyboard::start(!data_control))?;
+		let rule = rules::resolve(&CONFIG.rules, target.class.as_deref(), target.title.as_deref());
+
+		let pasted = if data_control {
+			paste_via_clipboard(text, || pollster::block_on(keyboard.press_shortcut(rule.paste)))
+		} else {
+			pollster::block_on(keyboard.paste_from_portal_clipboard(text, rule.paste))
+		};
+		pollster::block_on(keyboard.close());
+		pasted?;
+		Ok(Outcome::Inserted)
+	}
+}
+
+struct PortalKeyboard {
+	proxy:     RemoteDesktop<'static>,
+	session:   Session<'static, RemoteDesktop<'static>>,
+	clipboard: Option<Clipboard<'static>>,
+}
+
+impl PortalKeyboard {
+	/// Starts a remote desktop session with keyboard access, and with clipboard
+	/// access too when `with_clipboard` is set.
+	async fn start(with_clipboard: bool) -> Result<Self, InsertError> {
+		let proxy = RemoteDesktop::new()
+			.await
+			.map_err(|e| InsertError::Unsupported(format!("no RemoteDesktop portal: {e}")))?;
+		let session =
+			proxy.create_session().await.map_err(|e| failed("could not create session", e))?;
+
+		let restore_token = load_restore_token();
+		proxy
+			.select_devices(
+				&session,
+				DeviceType::Keyboard.into(),
+				restore_token.as_deref(),
+				PersistMode::ExplicitlyRevoked,
+			)
+			.await
+			.map_err(|e| failed("could not select keyboard", e))?;
+
+		// Clipboard access has to be asked for before the session starts.
+		let clipboard = if with_clipboard {
+			let clipboard = Clipboard::new()
+				.await
+				.map_err(|e| InsertError::Unsupported(format!("no clipboard portal: {e}")))?;
+			clipboard
+				.request(&session)
+				.await
+				.map_err(|e| failed("could not request clipboard access", e))?;
+			Some(clipboard)
+		} else {
+			None
+		};
+
+		let selected = proxy
+			.start(&session, None)
+			.await
+			.and_then(|request| request.response())
+			.map_err(|e| InsertError::Unsupported(format!("remote control was not granted: {e}")))?;
+
+		if !selected.devices().contains(DeviceType::Keyboard) {
+			let _ = session.close().await;
+			return Err(InsertError::Unsupported("keyboard access was not granted".into()));
+		}
+		// Tokens are single use, so each session hands back a fresh one. One for a
+		// grant without the keyboard would only restore that grant, so it is not
+		// kept.
+		if let Some(token) = selected.restore_token() {
+			store_restore_token(token);
+		}
+
+		Ok(Self { proxy, session, clipboard })
+	}
+
+	/// Offers `text` on the portal's clipboard, presses the paste shortcut and
+	/// hands the text to the target once it asks for it.
+	async fn paste_from_portal_clipboard(
+		&self,
+		text: &str,
+		shortcut: PasteShortcut,
+	) -> Result<(), InsertError> {
+		let Some(clipboard) = &self.clipboard else {
+			return Err(InsertError::Unsupported("clipboard access was not requested".into()));
+		};
+
+		let mut transfers = pin!(
+			clipboard
+				.receive_selection_transfer()
+				.await
+				.map_err(|e| failed("could not watch clipboard requests", e))?
+		);
+		clipboard
+			.set_selection(&self.session, TEXT_MIME_TYPES)
+			.await
+			.map_err(|e| failed("could not set the clipboard", e))?;
+		self.press_shortcut(shortcut).await?;
+
+		let requested =
+			async { transfers.next().await.map(|(_, mime_type, serial)| (mime_type, serial)) };
+		let timed_out = async {
+			Timer::after(PASTE_TIMEOUT).await;
+			None
+		};
+		let Some((mime_type, serial)) = requested.or(timed_out).await else {
+			warn!("the target never asked for the pasted text");
+			return Ok(());
+		};
+
+		let fd = clipboard
+			.selection_write(&self.session, serial)
+			.await
+			.map_err(|e| failed("could not answer the clipboard request", e))?;
+		let written = File::from(OwnedFd::from(fd)).write_all(text.as_bytes());
+		if let Err(e) = &written {
+			debug!(%mime_type, "clipboard request was abandoned: {e}");
+		}
+		clipboard
+			.selection_write_done(&self.session, serial, written.is_ok())
+			.await
+			.map_err(|e| failed("could not finish the clipboard transfer", e))
+	}
+
+	/// Presses the shortcut, letting go of every modifier that went down even
+	/// when a later key fails, so none stays held on the portal's keyboard.
+	async fn press_shortcut(&self, shortcut: PasteShortcut) -> Result<(), InsertError> {
+		let (modifiers, key) = shortcut_keys(shortcut);
+
+		let mut sent = Ok(());
+		let mut held = 0;
+		for modifier in modifiers {
+			sent = self.key(*modifier, KeyState::Pressed).await;
+			if sent.is_err() {
+				break;
+			}
+			held += 1;
+		}
+		if sent.is_ok() {
+			sent = self.key(key, KeyState::Pressed).await;
+			if sent.is_ok() {
+				sent = self.key(key, KeyState::Released).await;
+			}
+		}
+		for modifier in modifiers[..held].iter().rev() {
+			let released = self.key(*modifier, KeyState::Released).await;
+			sent = sent.and(released);
+		}
+		sent
+	}
+
+	async fn key(&self, code: u16, state: KeyState) -> Result<(), InsertError> {
+		self
+			.proxy
+			.notify_keyboard_keycode(&self.session, code.into(), state)
+			.await
+			.map_err(|e| failed("could not send paste shortcut", e))
+	}
+
+	async fn close(self) {
+		if let Err(e) = self.session.close().await {
+			debug!("could not close remote desktop session: {e}");
+		}
+	}
+}
+
+fn failed(context: &str, e: impl std::fmt::Display) -> InsertError {
+	InsertError::Failed(format!("{context}: {e}"))
+}
+
+fn restore_token_path() -> Option<PathBuf> { state_dir().map(|dir| dir.join(RESTORE_TOKEN_FILE)) }
+
+fn load_restore_token() -> Option<String> {
+	let token = fs::read_to_string(restore_token_path()?).ok()?;
+	let token = token.trim();
+	(!token.is_empty()).then(|| token.to_string())
+}
+
+fn store_restore_token(token: &str) {
+	let Some(path) = restore_token_path() else {
+		return;
+	};
+
+	let written =
+		path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|()| fs::write(&path, token));
+	if let Err(e) = written {
+		warn!(path = %path.display(), "could not save portal restore token: {e}");
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		collections::HashMap,
+		sync::{Arc, Mutex},
+	};
+
+	use zbus::{
+		message::Header,
+		zvariant::{OwnedObjectPath, OwnedValue, Value},
+	};
+
+	use super::*;
+	use crate::test_support::{PrivateBus, ScopedEnv, lock_env};
+
+	const KEYBOARD: u32 = 1;
+	const POINTER: u32 = 2;
+
+	/// What the stand-in portal answers `Start` with.
+	#[derive(Clone, Copy, PartialEq)]
+	enum Consent {
+		Keyboard,
+		PointerOnly,
+		Denied,
+	}
+
+	#[derive(Default)]
+	struct Seen {
+		restore_tokens: Vec<Option<String>>,
+		keycodes:       Vec<(i32, u32)>,
+	}
+
+	/// Stands in for xdg-desktop-portal's RemoteDesktop interface, answering
+	/// every request straight away and handing out numbered restore tokens. Key
+	/// events for `refused_key` fail.
+	struct MockRemoteDesktop {
+		consent:     Arc<Mutex<Consent>>,
+		seen:        Arc<Mutex<Seen>>,
+		refused_key: Arc<Mutex<Option<i32>>>,
+		issued:      Mutex<u32>,
+	}
+
+	/// The object path a portal derives from the caller's name and a token.
+	fn handle_path(
+		header: &Header<'_>,
+		kind: &str,
+		options: &HashMap<String, OwnedValue>,
+		key: &str,
+	) -> OwnedObjectPath {
+		let sender = header.sender().expect("calls carry a sender");
+		let caller = sender.trim_start_matches(':').replace('.', "_");
+		let token = options.get(key).and_then(|value| String::try_from(value.clone()).ok());
+		let path =
+			format!("/org/freedesktop/portal/desktop/{kind}/{caller}/{}", token.unwrap_or_default());
+		OwnedObjectPath::try_from(path).expect("tokens are valid path elements")
+	}
+
+	/// Emits the `Response` of a request and returns its handle.
+	async fn respond(
+		connection: &zbus::Connection,
+		header: &Header<'_>,
+		options: &HashMap<String, OwnedValue>,
+		code: u32,
+		results: HashMap<&str, Value<'_>>,
+	) -> OwnedObjectPath {
+		let request = handle_path(header, "request", options, "handle_token");
+		connection
+			.emit_signal(
+				header.sender().map(|sender| sender.as_str()),
+				&request,
+				"org.freedesktop.portal.Request",
+				"Response",
+				&(code, results),
+			)
+			.await
+			.expect("response signal is sent");
+		request
+	}
+
+	#[zbus::interface(name = "org.freedesktop.portal.RemoteDesktop")]
+	impl MockRemoteDesktop {
+		async fn create_session(
+			&self,
+			options: HashMap<String, OwnedValue>,
+			#[zbus(header)] header: Header<'_>,
+			#[zbus(connection)] connection: &zbus::Connection,
+		) -> OwnedObjectPath {
+			let session = handle_path(&header, "session", &options, "session_handle_token");
+			let results = HashMap::from([("session_handle", Value::from(session.as_str()))]);
+			respond(connection, &header, &options, 0, results).await
+		}
+
+		async fn select_devices(
+			&self,
+			_session: OwnedObjectPath,
+			options: HashMap<String, OwnedValue>,
+			#[zbus(header)] header: Header<'_>,
+			#[zbus(connection)] connection: &zbus::Connection,
+		) -> OwnedObjectPath {
+			let token =
+				options.get("restore_token").and_then(|value| String::try_from(value.clone()).ok());
+			self.seen.lock().unwrap().restore_tokens.push(token);
+			respond(connection, &header, &options, 0, HashMap::new()).await
+		}
+
+		async fn start(
+			&self,
+			_session: OwnedObjectPath,
+			_parent_window: String,
+			options: HashMap<String, OwnedValue>,
+			#[zbus(header)] header: Header<'_>,
+			#[zbus(connection)] connection: &zbus::Connection,
+		) -> OwnedObjectPath {
+			let consent = *self.consent.lock().unwrap();
+			let devices = match consent {
+				Consent::Denied => return respond(connection, &header, &options, 1, HashMap::new()).await,
+				Consent::Keyboard => KEYBOARD,
+				Consent::PointerOnly => POINTER,
+			};
+
+			let token = {
+				let mut issued = self.issued.lock().unwrap();
+				*issued += 1;
+				format!("token-{issued}")
+			};
+			let results =
+				HashMap::from([("devices", Value::from(devices)), ("restore_token", Value::from(token))]);
+			respond(connection, &header, &options, 0, results).await
+		}
+
+		fn notify_keyboard_keycode(
+			&self,
+			_session: OwnedObjectPath,
+			_options: HashMap<String, OwnedValue>,
+			keycode: i32,
+			state: u32,
+		) -> zbus::fdo::Result<()> {
+			if *self.refused_key.lock().unwrap() == Some(keycode) {
+				return Err(zbus::fdo::Error::Failed(format!("key {keycode} refused")));
+			}
+			self.seen.lock().unwrap().keycodes.push((keycode, state));
+			Ok(())
+		}
+
+		#[zbus(property)]
+		fn version(&self) -> u32 { 2 }
+	}
+
+	fn saved_token() -> Option<String> { load_restore_token() }
+
+	#[test]
+	fn restore_token_round_trip_and_refused_consent() {
+		let _env = lock_env();
+		let Some(bus) = PrivateBus::start() else {
+			return;
+		};
+		let state_home = std::env::temp_dir().join(format!("bob-portal-test-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&state_home);
+		let mut env = ScopedEnv::default();
+		env.set("XDG_STATE_HOME", &state_home);
+		env.set("DBUS_SESSION_BUS_ADDRESS", &bus.address);
+
+		let consent = Arc::new(Mutex::new(Consent::Keyboard));
+		let seen = Arc::new(Mutex::new(Seen::default()));
+		let refused_key = Arc::new(Mutex::new(None));
+		let portal = MockRemoteDesktop {
+			consent:     consent.clone(),
+			seen:        seen.clone(),
+			refused_key: refused_key.clone(),
+			issued:      Mutex::new(0),
+		};
+		let _server = zbus::blocking::connection::Builder::address(bus.address.as_str())
+			.and_then(|builder| builder.name("org.freedesktop.portal.Desktop"))
+			.and_then(|builder| builder.serve_at("/org/freedesktop/portal/desktop", portal))
+			.and_then(|builder| builder.build())
+			.expect("stand-in portal starts");
+
+		// The first session has no token to offer and stores the one handed back.
+		let keyboard = pollster::block_on(PortalKeyboard::start(false)).expect("consent is granted");
+		pollster::block_on(keyboard.press_shortcut(PasteShortcut::CtrlV)).expect("keys are sent");
+		assert_eq!(seen.lock().unwrap().keycodes, [(29, 1), (47, 1), (47, 0), (29, 0)]);
+
+		// A key that fails still has the modifiers let go of.
+		seen.lock().unwrap().keycodes.clear();
+		*refused_key.lock().unwrap() = Some(47);
+		let pressed = pollster::block_on(keyboard.press_shortcut(PasteShortcut::CtrlShiftV));
+		assert!(matches!(pressed, Err(InsertError::Failed(_))));
+		assert_eq!(seen.lock().unwrap().keycodes, [(29, 1), (42, 1), (42, 0), (29, 0)]);
+
+		pollster::block_on(keyboard.close());
+		assert_eq!(saved_token().as_deref(), Some("token-1"));
+
+		// The next one offers it and keeps the fresh token.
+		let keyboard = pollster::block_on(PortalKeyboard::start(false)).expect("consent is restored");
+		pollster::block_on(keyboard.close());
+		assert_eq!(seen.lock().unwrap().restore_tokens, [None, Some("token-1".to_string())]);
+		assert_eq!(saved_token().as_deref(), Some("token-2"));
+
+		// Refusing the dialog, or granting only a pointer, makes the backend
+		// step aside and leaves the stored token alone.
+		for refusal in [Consent::Denied, Consent::PointerOnly] {
+			*consent.lock().unwrap() = refusal;
+			let started = pollster::block_on(PortalKeyboard::start(false));
+			assert!(matches!(started, Err(InsertError::Unsupported(_))));
+		}
+		assert_eq!(saved_token().as_deref(), Some("token-2"));
+
+		let _ = fs::remove_dir_all(&state_home);
+	}
+}
@@ -0,0 +1,238 @@
+//! Per-application output rules keyed on the target window's class and title.
+
+use serde::Deserialize;
+
+use super::format::{OutputFormat, Presentation};
+
+/// Terminal window classes and Wayland app ids that need Ctrl+Shift+V instead
+/// of Ctrl+V. These back the built-in rules consulted after the user's own, and
+/// are matched whole so that `st` does not catch `steam`.
+const TERMINAL_CLASSES: &[&str] = &[
+	"kitty",
+	"alacritty",
+	"foot",
+	"footclient",
+	"wezterm",
+	"org.wezfurlong.wezterm",
+	"terminator",
+	"tilix",
+	"com.gexperts.tilix",
+	"gnome-terminal",
+	"gnome-terminal-server",
+	"org.gnome.terminal",
+	"konsole",
+	"org.kde.konsole",
+	"xterm",
+	"urxvt",
+	"st",
+	"st-256color",
+	"rio",
+	"ghostty",
+	"com.mitchellh.ghostty",
+];
+
+/// Electron window classes whose text input mangles emoji typed through XTest.
+/// These back built-in rules that paste instead of typing on X11, matched whole
+/// like the terminal classes.
+const XTEST_UNSAFE_CLASSES: &[&str] = &[
+	"code",
+	"code-oss",
+	"vscodium",
+	"discord",
+	"slack",
+	"obsidian",
+	"signal",
+	"element",
+	"teams-for-linux",
+	"microsoft teams - preview",
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum PasteShortcut {
+	#[default]
+	CtrlV,
+	CtrlShiftV,
+	ShiftInsert,
+}
+
+impl PasteShortcut {
+	/// Modifiers and key in the form Hyprland's `sendshortcut` dispatcher takes.
+	pub(crate) fn hyprland_keys(self) -> (&'static str, &'static str) {
+		match self {
+			Self::CtrlV => ("CONTROL", "V"),
+			Self::CtrlShiftV => ("CONTROL SHIFT", "V"),
+			Self::ShiftInsert => ("SHIFT", "Insert"),
+		}
+	}
+}
+
+/// A rule applied when the target window matches. `class` and `title` are
+/// case-insensitive substrings; a rule with neither never matches.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct AppRule {
+	pub class:        Option<String>,
+	pub title:        Option<String>,
+	pub format:       Option<OutputFormat>,
+	/// Presentation for emoji that also have a text form, overriding the
+	/// configured one.
+	pub presentation: Option<Presentation>,
+	pub paste:        Option<PasteShortcut>,
+	/// Paste through the clipboard instead of typing on X11.
+	pub prefer_paste: Option<bool>,
+	/// Built-in rules match the class whole rather than as a substring.
+	#[serde(skip)]
+	whole_class:      bool,
+}
+
+impl AppRule {
+	/// A built-in rule for the window class `class`, matched whole.
+	fn builtin(class: &str) -> Self {
+		Self { class: Some(class.to_string()), whole_class: true, ..Self::default() }
+	}
+
+	fn matches(&self, class: Option<&str>, title: Option<&str>) -> bool {
+		if self.class.is_none() && self.title.is_none() {
+			return false;
+		}
+
+		let field_matches = |pattern: &Option<String>, value: Option<&str>, whole: bool| match pattern {
+			None => true,
+			Some(pattern) => value.is_some_and(|value| {
+				let (value, pattern) = (value.to_lowercase(), pattern.to_lowercase());
+				if whole { value == pattern } else { value.contains(&pattern) }
+			}),
+		};
+
+		field_matches(&self.class, class, self.whole_class) && field_matches(&self.title, title, false)
+	}
+}
+
+/// The outcome of matching a window against every rule.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ResolvedRule {
+	/// Only set when a rule asked for a format; otherwise the configured default
+	/// applies.
+	pub format:       Option<OutputFormat>,
+	/// Only set when a rule asked for a presentation, like `format`.
+	pub presentation: Option<Presentation>,
+	pub paste:        PasteShortcut,
+	pub prefer_paste: bool,
+}
+
+/// Resolves the rules for a window. The user's rules are checked in order
+/// before the built-in ones, and for each setting the first matching rule that
+/// specifies it wins.
+pub(crate) fn resolve(
+	user_rules: &[AppRule],
+	class: Option<&str>,
+	title: Option<&str>,
+) -> ResolvedRule {
+	let terminals = TERMINAL_CLASSES.iter().map(|terminal| AppRule {
+		paste: Some(PasteShortcut::CtrlShiftV),
+		..AppRule::builtin(terminal)
+	});
+	let xtest_unsafe = XTEST_UNSAFE_CLASSES
+		.iter()
+		.map(|class| AppRule { prefer_paste: Some(true), ..AppRule::builtin(class) });
+	let builtin = terminals.chain(xtest_unsafe);
+
+	let mut format = None;
+	let mut presentation = None;
+	let mut paste = None;
+	let mut prefer_paste = None;
+	for rule in user_rules.iter().cloned().chain(builtin).filter(|rule| rule.matches(class, title)) {
+		format = format.or(rule.format);
+		presentation = presentation.or(rule.presentation);
+		paste = paste.or(rule.paste);
+		prefer_paste = prefer_paste.or(rule.prefer_paste);
+		if format.is_some() && presentation.is_some() && paste.is_some() && prefer_paste.is_some() {
+			break;
+		}
+	}
+
+	ResolvedRule {
+		format,
+		presentation,
+		paste: paste.unwrap_or_default(),
+		prefer_paste: prefer_paste.unwrap_or_default(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rule(class: Option<&str>, title: Option<&str>) -> AppRule {
+		AppRule { class: class.map(String::from), title: title.map(String::from), ..AppRule::default() }
+	}
+
+	#[test]
+	fn user_rules_override_built_ins() {
+		let user = [AppRule { paste: Some(PasteShortcut::ShiftInsert), ..rule(Some("kitty"), None) }];
+		assert_eq!(resolve(&user, Some("kitty"), None).paste, PasteShortcut::ShiftInsert);
+		assert_eq!(resolve(&[], Some("kitty"), None).paste, PasteShortcut::CtrlShiftV);
+	}
+
+	#[test]
+	fn the_first_rule_setting_a_field_wins() {
+		let user = [AppRule { format: Some(OutputFormat::Name), ..rule(None, Some("vim")) }, AppRule {
+			format: Some(OutputFormat::Shortcode),
+			presentation: Some(Presentation::Text),
+			..rule(Some("kitty"), None)
+		}];
+
+		let resolved = resolve(&user, Some("kitty"), Some("vim — notes.md"));
+		assert_eq!(resolved.format, Some(OutputFormat::Name));
+		assert_eq!(resolved.presentation, Some(Presentation::Text));
+		assert_eq!(resolved.paste, PasteShortcut::CtrlShiftV);
+
+		let resolved = resolve(&user, Some("kitty"), Some("zsh"));
+		assert_eq!(resolved.format, Some(OutputFormat::Shortcode));
+	}
+
+	#[test]
+	fn class_and_title_must_both_match() {
+		let user =
+			[AppRule { format: Some(OutputFormat::Name), ..rule(Some("firefox"), Some("github")) }];
+		assert_eq!(
+			resolve(&user, Some("firefox"), Some("GitHub - Issues")).format,
+			Some(OutputFormat::Name)
+		);
+		assert_eq!(resolve(&user, Some("firefox"), Some("Mail")).format, None);
+		assert_eq!(resolve(&user, None, Some("GitHub")).format, None);
+	}
+
+	#[test]
+	fn prefer_paste_and_paste_resolve_separately() {
+		let user = [AppRule { prefer_paste: Some(false), ..rule(Some("code"), None) }];
+		let resolved = resolve(&user, Some("Code"), None);
+		assert!(!resolved.prefer_paste);
+		assert_eq!(resolved.paste, PasteShortcut::CtrlV);
+
+		let resolved = resolve(&[], Some("Code"), None);
+		assert!(resolved.prefer_paste);
+		assert_eq!(resolved.paste, PasteShortcut::CtrlV);
+	}
+
+	#[test]
+	fn built_ins_match_whole_classes() {
+		assert_eq!(resolve(&[], Some("Steam"), None).paste, PasteShortcut::CtrlV);
+		assert!(!resolve(&[], Some("unicode-picker"), None).prefer_paste);
+		assert!(resolve(&[], Some("Slack"), None).prefer_paste);
+		assert_eq!(resolve(&[], Some("st-256color"), None).paste, PasteShortcut::CtrlShiftV);
+	}
+
+	#[test]
+	fn user_classes_match_substrings() {
+		let user = [AppRule { prefer_paste: Some(true), ..rule(Some("chrom"), None) }];
+		assert!(resolve(&user, Some("Chromium-browser"), None).prefer_paste);
+	}
+
+	#[test]
+	fn rules_without_class_or_title_never_match() {
+		let user = [AppRule { format: Some(OutputFormat::Name), ..AppRule::default() }];
+		assert_eq!(resolve(&user, Some("kitty"), Some("zsh")).format, None);
+	}
+}
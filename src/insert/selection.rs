@@ -0,0 +1,71 @@
+//! The PRIMARY selection: where inserted text can land for middle-click
+//! pasting, and where the picker can take its first search query from.
+
+use serde::Deserialize;
+
+/// Whether inserted text is also put on the PRIMARY selection.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrimarySelection {
+	/// Leave PRIMARY alone.
+	#[default]
+	Off,
+	/// Put inserted text on PRIMARY too, and copy fallbacks on both selections.
+	Also,
+	/// Put inserted text on PRIMARY, and copy fallbacks on PRIMARY only so the
+	/// clipboard is left alone.
+	Instead,
+}
+
+/// Longest selection taken as a search query; anything longer is prose rather
+/// than an emoji name.
+#[cfg(target_os = "linux")]
+const MAX_QUERY_CHARS: usize = 64;
+
+/// Makes `text` the PRIMARY selection.
+#[cfg(target_os = "linux")]
+pub(crate) fn set_primary(text: &str) -> Result<(), super::backend::InsertError> {
+	use super::backend::InsertError;
+	use crate::integration::{
+		linux::{LinuxSession, detect_linux_session},
+		x11,
+	};
+
+	let session = detect_linux_session();
+	let set = if session.is_wayland() {
+		super::wayland::wl_copy_primary(text)
+	} else if session == LinuxSession::X11 {
+		x11::set_primary(text.as_bytes())
+	} else {
+		return Err(InsertError::Unsupported("no selection support in this session".into()));
+	};
+	set.map_err(|e| InsertError::Failed(format!("could not set the primary selection: {e}")))
+}
+
+/// The currently selected text, cleaned up for use as a search query.
+#[cfg(target_os = "linux")]
+pub(crate) fn read_query() -> Option<String> {
+	use tracing::warn;
+
+	use crate::integration::{
+		linux::{LinuxSession, detect_linux_session},
+		x11,
+	};
+
+	let session = detect_linux_session();
+	let read = if session.is_wayland() {
+		super::wayland::wl_read_primary()
+	} else if session == LinuxSession::X11 {
+		x11::read_primary().map(|contents| contents.map(|c| String::from_utf8_lossy(&c).into_owned()))
+	} else {
+		return None;
+	};
+
+	let selected =
+		read.inspect_err(|e| warn!("failed to read the primary selection: {e}")).ok()??;
+	let query = selected.lines().next()?.trim();
+	(!query.is_empty() && query.chars().count() <= MAX_QUERY_CHARS).then(|| query.to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn read_query() -> Option<String> { None }
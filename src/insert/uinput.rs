@@ -0,0 +1,357 @@
+//! Paste insertion for GNOME, KDE and other Wayland desktops without a
+//! text-entry protocol: the text goes on the clipboard and a short-lived
+//! `/dev/uinput` keyboard presses the paste shortcut.
+
+use std::{
+	ffi::{CStr, CString},
+	fs::{self, File, OpenOptions},
+	io::{self, Write},
+	mem,
+	os::{
+		fd::{AsRawFd, FromRawFd, OwnedFd},
+		unix::{ffi::OsStrExt, fs::OpenOptionsExt},
+	},
+	path::{Path, PathBuf},
+	sync::Once,
+	thread,
+	time::Duration,
+};
+
+use tracing::debug;
+
+use super::{
+	backend::{BackendKind, InsertBackend, InsertError, InsertTarget, Outcome},
+	notify::Notify,
+	rules::{self, PasteShortcut},
+	wayland::paste_via_clipboard,
+	x11_paste::paste_via_x11_clipboard,
+};
+use crate::{
+	config::CONFIG,
+	integration::{
+		linux::{detect_linux_session, wayland_capabilities},
+		notifications::notify,
+	},
+};
+
+pub(crate) const UINPUT_PATH: &str = "/dev/uinput";
+
+/// How long the compositor gets to open a freshly created device before it is
+/// sent keys anyway; events sent earlier are silently dropped.
+const DEVICE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Time given to the compositor instead when the device cannot be watched.
+const DEVICE_SETTLE: Duration = Duration::from_millis(200);
+
+// Values from `linux/input-event-codes.h` and `linux/uinput.h`.
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const SYN_REPORT: u16 = 0;
+const BUS_VIRTUAL: u16 = 0x06;
+
+pub(crate) const KEY_BACKSPACE: u16 = 14;
+pub(crate) const KEY_LEFTCTRL: u16 = 29;
+pub(crate) const KEY_LEFTSHIFT: u16 = 42;
+const KEY_V: u16 = 47;
+const KEY_INSERT: u16 = 110;
+
+const UI_DEV_CREATE: libc::c_ulong = 0x5501;
+const UI_DEV_DESTROY: libc::c_ulong = 0x5502;
+const UI_DEV_SETUP: libc::c_ulong = 0x405c5503;
+const UI_SET_EVBIT: libc::c_ulong = 0x40045564;
+const UI_SET_KEYBIT: libc::c_ulong = 0x40045565;
+/// `UI_GET_SYSNAME(64)`.
+const UI_GET_SYSNAME: libc::c_ulong = 0x8040552c;
+
+#[repr(C)]
+struct InputId {
+	bustype: u16,
+	vendor:  u16,
+	product: u16,
+	version: u16,
+}
+
+#[repr(C)]
+struct UinputSetup {
+	id:             InputId,
+	name:           [u8; 80],
+	ff_effects_max: u32,
+}
+
+#[repr(C)]
+struct InputEvent {
+	time:  libc::timeval,
+	kind:  u16,
+	code:  u16,
+	value: i32,
+}
+
+/// Copies the text and presses the paste shortcut on a virtual keyboard. The
+/// text goes on the Wayland clipboard where the compositor lets clients set it,
+/// and otherwise on the XWayland one, which GNOME mirrors to Wayland windows.
+///
+/// The key codes are physical positions that the compositor maps through the
+/// active layout, so the shortcut is only right where V sits in its US QWERTY
+/// place. On Dvorak that key types `.` and Ctrl+V does not paste; a `paste`
+/// rule of `shift-insert` avoids this, as Insert is the same on every layout.
+pub(crate) struct UinputPasteBackend;
+
+impl InsertBackend for UinputPasteBackend {
+	fn kind(&self) -> BackendKind { BackendKind::UinputPaste }
+
+	fn available(&self) -> bool {
+		detect_linux_session().is_wayland() && Path::new(UINPUT_PATH).exists()
+	}
+
+	fn insert(&self, text: &str, target: &InsertTarget) -> Result<Outcome, InsertError> {
+		let data_control = wayland_capabilities().data_control;
+		if !data_control && std::env::var_os("DISPLAY").is_none() {
+			return Err(InsertError::Unsupported(
+				"the compositor lets no client set the clipboard, and there is no XWayland".into(),
+			));
+		}
+
+		// Create the device before touching the clipboard so a permission problem
+		// leaves the clipboard as it was.
+		let keyboard = UinputKeyboard::create().inspect_err(report_open_problem)?;
+		let rule = rules::resolve(&CONFIG.rules, target.class.as_deref(), target.title.as_deref());
+		let paste = || {
+			keyboard
+				.press_shortcut(rule.paste)
+				.map_err(|e| InsertError::Failed(format!("could not send paste shortcut: {e}")))
+		};
+
+		if data_control {
+			paste_via_clipboard(text, paste)?;
+		} else {
+			paste_via_x11_clipboard(text, paste)?;
+		}
+		Ok(Outcome::Inserted)
+	}
+}
+
+/// Tells the user, once per run, why `/dev/uinput` could not be opened and how
+/// to allow it. The chain moves on to the next backend, so the reason would
+/// otherwise only reach the log.
+fn report_open_problem(e: &InsertError) {
+	static REPORTED: Once = Once::new();
+
+	if let InsertError::Unsupported(reason) = e
+		&& CONFIG.notify != Notify::Never
+	{
+		REPORTED.call_once(|| notify("Paste shortcut not available", reason));
+	}
+}
+
+pub(crate) struct UinputKeyboard {
+	device: File,
+}
+
+impl UinputKeyboard {
+	pub(crate) fn create() -> Result<Self, InsertError> {
+		let device = OpenOptions::new()
+			.write(true)
+			.custom_flags(libc::O_NONBLOCK)
+			.open(UINPUT_PATH)
+			.map_err(explain_open_error)?;
+
+		let fd = device.as_raw_fd();
+		let ioctl = |request: libc::c_ulong, arg: libc::c_ulong| -> io::Result<()> {
+			if unsafe { libc::ioctl(fd, request, arg) } < 0 {
+				return Err(io::Error::last_os_error());
+			}
+			Ok(())
+		};
+
+		let setup_failed =
+			|e: io::Error| InsertError::Failed(format!("could not set up uinput device: {e}"));
+
+		ioctl(UI_SET_EVBIT, EV_KEY.into()).map_err(setup_failed)?;
+		for key in [KEY_LEFTCTRL, KEY_LEFTSHIFT, KEY_V, KEY_INSERT, KEY_BACKSPACE] {
+			ioctl(UI_SET_KEYBIT, key.into()).map_err(setup_failed)?;
+		}
+
+		let mut setup = UinputSetup {
+			id:             InputId { bustype: BUS_VIRTUAL, vendor: 0x1209, product: 0xb0b, version: 1 },
+			name:           [0; 80],
+			ff_effects_max: 0,
+		};
+		let name = b"B.O.B virtual keyboard";
+		setup.name[..name.len()].copy_from_slice(name);
+
+		ioctl(UI_DEV_SETUP, &setup as *const UinputSetup as libc::c_ulong).map_err(setup_failed)?;
+		ioctl(UI_DEV_CREATE, 0).map_err(setup_failed)?;
+
+		wait_until_opened(&device);
+		Ok(Self { device })
+	}
+
+	pub(crate) fn press_shortcut(&self, shortcut: PasteShortcut) -> io::Result<()> {
+		let (modifiers, key) = shortcut_keys(shortcut);
+
+		for modifier in modifiers {
+			self.key(*modifier, true)?;
+		}
+		self.tap(key)?;
+		for modifier in modifiers.iter().rev() {
+			self.key(*modifier, false)?;
+		}
+		Ok(())
+	}
+
+	pub(crate) fn tap(&self, key: u16) -> io::Result<()> {
+		self.key(key, true)?;
+		self.key(key, false)
+	}
+
+	fn key(&self, code: u16, pressed: bool) -> io::Result<()> {
+		self.emit(EV_KEY, code, pressed.into())?;
+		self.emit(EV_SYN, SYN_REPORT, 0)
+	}
+
+	fn emit(&self, kind: u16, code: u16, value: i32) -> io::Result<()> {
+		let event = InputEvent { time: libc::timeval { tv_sec: 0, tv_usec: 0 }, kind, code, value };
+		let bytes = unsafe {
+			std::slice::from_raw_parts(
+				&event as *const InputEvent as *const u8,
+				mem::size_of::<InputEvent>(),
+			)
+		};
+		(&self.device).write_all(bytes)
+	}
+}
+
+/// Waits for the event node of a new device to be opened, which the compositor
+/// does once it has picked the device up.
+fn wait_until_opened(device: &File) {
+	match event_node(device).and_then(|node| wait_for_open(&node, DEVICE_TIMEOUT)) {
+		Ok(true) => {}
+		Ok(false) => debug!("nothing opened the virtual keyboard in time; sending keys anyway"),
+		Err(e) => {
+			debug!("could not watch the virtual keyboard being opened, waiting instead: {e}");
+			thread::sleep(DEVICE_SETTLE);
+		}
+	}
+}
+
+/// The `/dev/input/event*` node the kernel created for a uinput device.
+fn event_node(device: &File) -> io::Result<PathBuf> {
+	let mut sysname = [0u8; 64];
+	if unsafe { libc::ioctl(device.as_raw_fd(), UI_GET_SYSNAME, sysname.as_mut_ptr()) } < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	let sysname = CStr::from_bytes_until_nul(&sysname)
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+		.to_string_lossy();
+
+	fs::read_dir(Path::new("/sys/devices/virtual/input").join(&*sysname))?
+		.flatten()
+		.map(|entry| entry.file_name())
+		.find(|name| name.as_bytes().starts_with(b"event"))
+		.map(|name| Path::new("/dev/input").join(name))
+		.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{sysname} has no event node")))
+}
+
+/// Waits for something to open `node`; `false` on timeout.
+fn wait_for_open(node: &Path, timeout: Duration) -> io::Result<bool> {
+	let inotify = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+	if inotify < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	let inotify = unsafe { OwnedFd::from_raw_fd(inotify) };
+
+	let path = CString::new(node.as_os_str().as_bytes())?;
+	if unsafe { libc::inotify_add_watch(inotify.as_raw_fd(), path.as_ptr(), libc::IN_OPEN) } < 0 {
+		return Err(io::Error::last_os_error());
+	}
+
+	// Opening is the only event watched for, so any event at all means it
+	// happened.
+	let mut fd = libc::pollfd { fd: inotify.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+	let millis = timeout.as_millis().min(i32::MAX as u128) as i32;
+	match unsafe { libc::poll(&mut fd, 1, millis) } {
+		..0 => Err(io::Error::last_os_error()),
+		0 => Ok(false),
+		_ => Ok(true),
+	}
+}
+
+/// The evdev modifiers to hold and the key to tap for a paste shortcut.
+pub(crate) fn shortcut_keys(shortcut: PasteShortcut) -> (&'static [u16], u16) {
+	match shortcut {
+		PasteShortcut::CtrlV => (&[KEY_LEFTCTRL], KEY_V),
+		PasteShortcut::CtrlShiftV => (&[KEY_LEFTCTRL, KEY_LEFTSHIFT], KEY_V),
+		PasteShortcut::ShiftInsert => (&[KEY_LEFTSHIFT], KEY_INSERT),
+	}
+}
+
+impl Drop for UinputKeyboard {
+	fn drop(&mut self) {
+		unsafe {
+			libc::ioctl(self.device.as_raw_fd(), UI_DEV_DESTROY);
+		}
+	}
+}
+
+/// Turns the usual ways opening `/dev/uinput` fails into something the user can
+/// act on.
+fn explain_open_error(e: io::Error) -> InsertError {
+	match e.kind() {
+		io::ErrorKind::PermissionDenied => InsertError::Unsupported(format!(
+			"no permission to open {UINPUT_PATH} ({e}); allow it with a udev rule such as \
+			 `KERNEL==\"uinput\", GROUP=\"input\", MODE=\"0660\", OPTIONS+=\"static_node=uinput\"` \
+			 and add yourself to the `input` group, then log in again"
+		)),
+		io::ErrorKind::NotFound => InsertError::Unsupported(format!(
+			"{UINPUT_PATH} does not exist ({e}); load the module with `modprobe uinput`"
+		)),
+		_ => InsertError::Failed(format!("could not open {UINPUT_PATH}: {e}")),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn permission_problems_explain_the_fix() {
+		let e = explain_open_error(io::Error::from(io::ErrorKind::PermissionDenied));
+		let InsertError::Unsupported(reason) = e else {
+			panic!("expected unsupported, got {e}");
+		};
+		assert!(reason.contains("udev rule"));
+		assert!(reason.contains("`input` group"));
+	}
+
+	#[test]
+	fn notices_the_node_being_opened() {
+		let node = std::env::temp_dir().join(format!("bob-uinput-node-{}", std::process::id()));
+		File::create(&node).unwrap();
+
+		assert!(!wait_for_open(&node, Duration::from_millis(20)).unwrap());
+
+		let opener = {
+			let node = node.clone();
+			thread::spawn(move || {
+				thread::sleep(Duration::from_millis(20));
+				File::open(node).unwrap();
+			})
+		};
+		assert!(wait_for_open(&node, Duration::from_secs(5)).unwrap());
+		opener.join().unwrap();
+		let _ = fs::remove_file(node);
+	}
+
+	#[test]
+	fn presses_shortcuts_on_a_virtual_keyboard() {
+		if OpenOptions::new().write(true).open(UINPUT_PATH).is_err() {
+			eprintln!("skipping: {UINPUT_PATH} is not writable");
+			return;
+		}
+
+		let keyboard = UinputKeyboard::create().unwrap();
+		for shortcut in [PasteShortcut::CtrlV, PasteShortcut::CtrlShiftV, PasteShortcut::ShiftInsert] {
+			keyboard.press_shortcut(shortcut).unwrap();
+		}
+	}
+}
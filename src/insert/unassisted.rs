@@ -1,14 +1,37 @@
 //! Fallback insertion by copying to clipboard when direct input is unavailable.
 
-use tracing::{error, warn};
-
-pub(crate) fn copy_to_clipboard_wayland(emoji: &str) {
-	if let Err(e) = super::wayland::wl_copy(emoji) {
-		error!("failed to copy emoji to clipboard: {e}");
-	} else {
-		warn!(
-			"non-Hyprland Wayland compositor detected; \
-			 emoji copied to clipboard — paste with Ctrl+V"
-		);
+use tracing::warn;
+
+use super::{
+	backend::{BackendKind, InsertBackend, InsertError, InsertTarget, Outcome},
+	selection::{self, PrimarySelection},
+};
+use crate::{config::CONFIG, integration::linux::detect_linux_session};
+
+/// Leaves the text on the Wayland clipboard, or the primary selection if so
+/// configured, for the user to paste.
+pub(crate) struct ClipboardBackend;
+
+impl InsertBackend for ClipboardBackend {
+	fn kind(&self) -> BackendKind { BackendKind::Clipboard }
+
+	fn available(&self) -> bool { detect_linux_session().is_wayland() }
+
+	fn insert(&self, text: &str, _target: &InsertTarget) -> Result<Outcome, InsertError> {
+		if CONFIG.primary_selection != PrimarySelection::Instead {
+			copy_to_clipboard_wayland(text)?;
+		}
+		if CONFIG.primary_selection != PrimarySelection::Off {
+			selection::set_primary(text)?;
+			warn!("emoji put on the primary selection — paste with middle-click");
+		}
+		Ok(Outcome::Copied)
 	}
 }
+
+pub(crate) fn copy_to_clipboard_wayland(emoji: &str) -> Result<(), InsertError> {
+	super::wayland::wl_copy(emoji, CONFIG.mark_fallback_copies)
+		.map_err(|e| InsertError::Failed(format!("could not copy emoji to clipboard: {e}")))?;
+	warn!("emoji copied to clipboard — paste with Ctrl+V");
+	Ok(())
+}
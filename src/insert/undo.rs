@@ -0,0 +1,200 @@
+//! Undoing the last insertion by sending one BackSpace per grapheme to the
+//! window it went to.
+//!
+//! The insertion is remembered in memory only, unlike the history, so only the
+//! process that made it can undo it: in practice the service, whose undo
+//! hotkey takes back what its own picker, favorites and re-insert hotkey put
+//! in. A `reinsert` run from the command line exits before anything could ask
+//! it to undo, and the service never learns of it.
+
+use std::{path::Path, sync::Mutex};
+
+use tracing::debug;
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{
+	automated::press_backspace_enigo,
+	backend::{InsertError, InsertTarget},
+	uinput::{KEY_BACKSPACE, UINPUT_PATH, UinputKeyboard},
+	virtual_keyboard,
+};
+use crate::integration::linux::{
+	LinuxSession, capture_focused_window, detect_linux_session, wayland_capabilities,
+};
+
+struct Insertion {
+	text:   String,
+	target: InsertTarget,
+}
+
+static LAST_INSERTION: Mutex<Option<Insertion>> = Mutex::new(None);
+
+/// Records text that landed in `target`, so it can be taken back.
+pub(crate) fn remember(text: &str, target: &InsertTarget) {
+	let insertion = Insertion { text: text.to_string(), target: target.clone() };
+	*LAST_INSERTION.lock().unwrap_or_else(|e| e.into_inner()) = Some(insertion);
+}
+
+/// Drops the recorded insertion; after a later insertion failed or was only
+/// copied, undoing the earlier one would erase text the user did not expect.
+pub(crate) fn forget() { *LAST_INSERTION.lock().unwrap_or_else(|e| e.into_inner()) = None; }
+
+/// Deletes the last insertion from its window, provided that window still has
+/// focus. An insertion is only undone once.
+pub(crate) fn undo_last() -> Result<(), InsertError> {
+	let mut last = LAST_INSERTION.lock().unwrap_or_else(|e| e.into_inner());
+	undo(&mut last, capture_focused_window, erase)
+}
+
+/// Takes back `last` with `erase` if `focused` reports its window, clearing it
+/// once erased.
+fn undo(
+	last: &mut Option<Insertion>,
+	focused: impl FnOnce() -> InsertTarget,
+	erase: impl FnOnce(usize) -> Result<(), InsertError>,
+) -> Result<(), InsertError> {
+	let Some(insertion) = last.as_ref() else {
+		return Err(InsertError::Unsupported("nothing has been inserted yet".into()));
+	};
+
+	match insertion.target.same_window(&focused()) {
+		Some(true) => {}
+		Some(false) => {
+			return Err(InsertError::Unsupported(
+				"the window the text went to no longer has focus".into(),
+			));
+		}
+		None => {
+			return Err(InsertError::Unsupported(
+				"cannot tell whether the window the text went to still has focus".into(),
+			));
+		}
+	}
+
+	let count = insertion.text.graphemes(true).count();
+	debug!(text = %insertion.text, count, "undoing last insertion");
+	erase(count)?;
+	*last = None;
+	Ok(())
+}
+
+/// Presses BackSpace `count` times with whatever synthetic input the session
+/// allows.
+fn erase(count: usize) -> Result<(), InsertError> {
+	super::wait_for_shortcut_release();
+
+	let session = detect_linux_session();
+	if session == LinuxSession::X11 {
+		return press_backspace_enigo(count);
+	}
+	if !session.is_wayland() {
+		return Err(InsertError::Unsupported("no synthetic input in this session".into()));
+	}
+
+	if wayland_capabilities().virtual_keyboard {
+		return virtual_keyboard::press_backspace(count);
+	}
+	if Path::new(UINPUT_PATH).exists() {
+		let keyboard = UinputKeyboard::create()?;
+		for _ in 0..count {
+			keyboard
+				.tap(KEY_BACKSPACE)
+				.map_err(|e| InsertError::Failed(format!("could not send BackSpace: {e}")))?;
+		}
+		return Ok(());
+	}
+	Err(InsertError::Unsupported("no way to send keys in this Wayland session".into()))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::cell::RefCell;
+
+	use super::*;
+
+	fn window(con_id: i64) -> InsertTarget {
+		InsertTarget { i3_con_id: Some(con_id), ..Default::default() }
+	}
+
+	fn inserted(text: &str, con_id: i64) -> Option<Insertion> {
+		Some(Insertion { text: text.to_string(), target: window(con_id) })
+	}
+
+	/// Stands in for the keyboard, recording how many BackSpaces were asked for.
+	struct FakeKeyboard {
+		erased: RefCell<Vec<usize>>,
+		reply:  fn() -> Result<(), InsertError>,
+	}
+
+	impl FakeKeyboard {
+		fn working() -> Self { Self { erased: RefCell::default(), reply: || Ok(()) } }
+
+		fn erase(&self, count: usize) -> Result<(), InsertError> {
+			self.erased.borrow_mut().push(count);
+			(self.reply)()
+		}
+	}
+
+	#[test]
+	fn a_forgotten_insertion_is_not_undone() {
+		remember("🦀", &InsertTarget::default());
+		forget();
+		assert!(
+			matches!(undo_last(), Err(InsertError::Unsupported(reason)) if reason.contains("nothing"))
+		);
+	}
+
+	#[test]
+	fn erases_one_backspace_per_grapheme_in_the_same_window() {
+		let keyboard = FakeKeyboard::working();
+		let mut last = inserted("👨‍👩‍👧a🇩🇪", 42);
+
+		undo(&mut last, || window(42), |count| keyboard.erase(count)).expect("undo succeeds");
+		assert_eq!(*keyboard.erased.borrow(), [3]);
+		assert!(last.is_none());
+
+		let again = undo(&mut last, || window(42), |count| keyboard.erase(count));
+		assert!(matches!(again, Err(InsertError::Unsupported(reason)) if reason.contains("nothing")));
+		assert_eq!(*keyboard.erased.borrow(), [3]);
+	}
+
+	#[test]
+	fn refuses_once_another_window_has_focus() {
+		let keyboard = FakeKeyboard::working();
+		let mut last = inserted("🦀", 42);
+
+		let refused = undo(&mut last, || window(11), |count| keyboard.erase(count));
+		assert!(
+			matches!(refused, Err(InsertError::Unsupported(reason)) if reason.contains("no longer"))
+		);
+		assert!(keyboard.erased.borrow().is_empty());
+		assert!(last.is_some(), "the insertion can still be undone from its window");
+	}
+
+	#[test]
+	fn refuses_when_the_windows_cannot_be_compared() {
+		let keyboard = FakeKeyboard::working();
+		let mut last = inserted("🦀", 42);
+		let title_only = || InsertTarget { title: Some("~".into()), ..Default::default() };
+
+		let refused = undo(&mut last, title_only, |count| keyboard.erase(count));
+		assert!(
+			matches!(refused, Err(InsertError::Unsupported(reason)) if reason.contains("cannot tell"))
+		);
+		assert!(keyboard.erased.borrow().is_empty());
+	}
+
+	#[test]
+	fn a_failed_erase_can_be_retried() {
+		let keyboard = FakeKeyboard {
+			erased: RefCell::default(),
+			reply:  || Err(InsertError::Failed("no keyboard".into())),
+		};
+		let mut last = inserted("🦀", 42);
+
+		let failed = undo(&mut last, || window(42), |count| keyboard.erase(count));
+		assert!(matches!(failed, Err(InsertError::Failed(_))));
+		assert_eq!(*keyboard.erased.borrow(), [1]);
+		assert!(last.is_some());
+	}
+}
@@ -0,0 +1,233 @@
+//! Characters whose look depends on their variation selector, from Unicode's
+//! emoji data.
+
+/// Bases of emoji variation sequences that default to the text presentation
+/// (`Emoji_Presentation=No`), in code point order. Characters such as `✅` also
+/// have sequences but already show as emoji without a selector, so they are
+/// left out.
+///
+/// Taken from the `starts_emoji_presentation_seq` table of the unicode-width
+/// 0.2.2 crate, which that crate generates from Unicode 17.0's
+/// `emoji-variation-sequences.txt`, rather than from the Unicode files
+/// directly. Update it along with a newer unicode-width.
+pub(crate) const TEXT_DEFAULT_BASES: &[char] = &[
+	'\u{0023}',
+	'\u{002A}',
+	'\u{0030}',
+	'\u{0031}',
+	'\u{0032}',
+	'\u{0033}',
+	'\u{0034}',
+	'\u{0035}',
+	'\u{0036}',
+	'\u{0037}',
+	'\u{0038}',
+	'\u{0039}',
+	'\u{00A9}',
+	'\u{00AE}',
+	'\u{203C}',
+	'\u{2049}',
+	'\u{2122}',
+	'\u{2139}',
+	'\u{2194}',
+	'\u{2195}',
+	'\u{2196}',
+	'\u{2197}',
+	'\u{2198}',
+	'\u{2199}',
+	'\u{21A9}',
+	'\u{21AA}',
+	'\u{2328}',
+	'\u{23CF}',
+	'\u{23ED}',
+	'\u{23EE}',
+	'\u{23EF}',
+	'\u{23F1}',
+	'\u{23F2}',
+	'\u{23F8}',
+	'\u{23F9}',
+	'\u{23FA}',
+	'\u{24C2}',
+	'\u{25AA}',
+	'\u{25AB}',
+	'\u{25B6}',
+	'\u{25C0}',
+	'\u{25FB}',
+	'\u{25FC}',
+	'\u{2600}',
+	'\u{2601}',
+	'\u{2602}',
+	'\u{2603}',
+	'\u{2604}',
+	'\u{260E}',
+	'\u{2611}',
+	'\u{2618}',
+	'\u{261D}',
+	'\u{2620}',
+	'\u{2622}',
+	'\u{2623}',
+	'\u{2626}',
+	'\u{262A}',
+	'\u{262E}',
+	'\u{262F}',
+	'\u{2638}',
+	'\u{2639}',
+	'\u{263A}',
+	'\u{2640}',
+	'\u{2642}',
+	'\u{265F}',
+	'\u{2660}',
+	'\u{2663}',
+	'\u{2665}',
+	'\u{2666}',
+	'\u{2668}',
+	'\u{267B}',
+	'\u{267E}',
+	'\u{2692}',
+	'\u{2694}',
+	'\u{2695}',
+	'\u{2696}',
+	'\u{2697}',
+	'\u{2699}',
+	'\u{269B}',
+	'\u{269C}',
+	'\u{26A0}',
+	'\u{26A7}',
+	'\u{26B0}',
+	'\u{26B1}',
+	'\u{26C8}',
+	'\u{26CF}',
+	'\u{26D1}',
+	'\u{26D3}',
+	'\u{26E9}',
+	'\u{26F0}',
+	'\u{26F1}',
+	'\u{26F4}',
+	'\u{26F7}',
+	'\u{26F8}',
+	'\u{26F9}',
+	'\u{2702}',
+	'\u{2708}',
+	'\u{2709}',
+	'\u{270C}',
+	'\u{270D}',
+	'\u{270F}',
+	'\u{2712}',
+	'\u{2714}',
+	'\u{2716}',
+	'\u{271D}',
+	'\u{2721}',
+	'\u{2733}',
+	'\u{2734}',
+	'\u{2744}',
+	'\u{2747}',
+	'\u{2763}',
+	'\u{2764}',
+	'\u{27A1}',
+	'\u{2934}',
+	'\u{2935}',
+	'\u{2B05}',
+	'\u{2B06}',
+	'\u{2B07}',
+	'\u{3030}',
+	'\u{303D}',
+	'\u{3297}',
+	'\u{3299}',
+	'\u{1F170}',
+	'\u{1F171}',
+	'\u{1F17E}',
+	'\u{1F17F}',
+	'\u{1F202}',
+	'\u{1F237}',
+	'\u{1F321}',
+	'\u{1F324}',
+	'\u{1F325}',
+	'\u{1F326}',
+	'\u{1F327}',
+	'\u{1F328}',
+	'\u{1F329}',
+	'\u{1F32A}',
+	'\u{1F32B}',
+	'\u{1F32C}',
+	'\u{1F336}',
+	'\u{1F37D}',
+	'\u{1F396}',
+	'\u{1F397}',
+	'\u{1F399}',
+	'\u{1F39A}',
+	'\u{1F39B}',
+	'\u{1F39E}',
+	'\u{1F39F}',
+	'\u{1F3CB}',
+	'\u{1F3CC}',
+	'\u{1F3CD}',
+	'\u{1F3CE}',
+	'\u{1F3D4}',
+	'\u{1F3D5}',
+	'\u{1F3D6}',
+	'\u{1F3D7}',
+	'\u{1F3D8}',
+	'\u{1F3D9}',
+	'\u{1F3DA}',
+	'\u{1F3DB}',
+	'\u{1F3DC}',
+	'\u{1F3DD}',
+	'\u{1F3DE}',
+	'\u{1F3DF}',
+	'\u{1F3F3}',
+	'\u{1F3F5}',
+	'\u{1F3F7}',
+	'\u{1F43F}',
+	'\u{1F441}',
+	'\u{1F4FD}',
+	'\u{1F549}',
+	'\u{1F54A}',
+	'\u{1F56F}',
+	'\u{1F570}',
+	'\u{1F573}',
+	'\u{1F574}',
+	'\u{1F575}',
+	'\u{1F576}',
+	'\u{1F577}',
+	'\u{1F578}',
+	'\u{1F579}',
+	'\u{1F587}',
+	'\u{1F58A}',
+	'\u{1F58B}',
+	'\u{1F58C}',
+	'\u{1F58D}',
+	'\u{1F590}',
+	'\u{1F5A5}',
+	'\u{1F5A8}',
+	'\u{1F5B1}',
+	'\u{1F5B2}',
+	'\u{1F5BC}',
+	'\u{1F5C2}',
+	'\u{1F5C3}',
+	'\u{1F5C4}',
+	'\u{1F5D1}',
+	'\u{1F5D2}',
+	'\u{1F5D3}',
+	'\u{1F5DC}',
+	'\u{1F5DD}',
+	'\u{1F5DE}',
+	'\u{1F5E1}',
+	'\u{1F5E3}',
+	'\u{1F5E8}',
+	'\u{1F5EF}',
+	'\u{1F5F3}',
+	'\u{1F5FA}',
+	'\u{1F6CB}',
+	'\u{1F6CD}',
+	'\u{1F6CE}',
+	'\u{1F6CF}',
+	'\u{1F6E0}',
+	'\u{1F6E1}',
+	'\u{1F6E2}',
+	'\u{1F6E3}',
+	'\u{1F6E4}',
+	'\u{1F6E5}',
+	'\u{1F6E9}',
+	'\u{1F6F0}',
+	'\u{1F6F3}',
+];
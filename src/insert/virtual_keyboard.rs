@@ -0,0 +1,332 @@
+//! Direct typing on generic Wayland compositors through the
+//! `zwp_virtual_keyboard_v1` protocol, using a throwaway keymap that binds one
+//! keycode to each code point being typed.
+
+use std::{
+	ffi::{CStr, CString},
+	fs::File,
+	io::Write,
+	os::fd::{AsFd, FromRawFd},
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use tracing::{debug, warn};
+use wayland_client::{
+	Connection, Dispatch, EventQueue, QueueHandle, delegate_noop,
+	globals::{GlobalListContents, registry_queue_init},
+	protocol::{wl_registry, wl_seat},
+};
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::{
+	zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1,
+	zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1,
+};
+
+use super::{
+	FOCUS_TIMEOUT,
+	backend::{BackendKind, InsertBackend, InsertError, InsertTarget, Outcome},
+	rules::PasteShortcut,
+	uinput::{KEY_BACKSPACE, KEY_LEFTCTRL, KEY_LEFTSHIFT, shortcut_keys},
+};
+use crate::integration::{foreign_toplevel::ActivationWatch, linux::wayland_capabilities};
+
+/// `wl_keyboard.keymap_format.xkb_v1`.
+const KEYMAP_FORMAT_XKB_V1: u32 = 1;
+
+/// xkb keycodes are evdev keycodes offset by 8.
+const XKB_KEYCODE_OFFSET: u32 = 8;
+
+/// Binds the keys of every paste shortcut, and BackSpace for undoing, at their
+/// usual evdev positions.
+const SHORTCUT_KEYMAP: &CStr = c"xkb_keymap {
+xkb_keycodes \"bob\" { include \"evdev\" };
+xkb_types \"bob\" { include \"complete\" };
+xkb_compatibility \"bob\" { include \"complete\" };
+xkb_symbols \"bob\" {
+key <LCTL> { [ Control_L ] };
+key <LFSH> { [ Shift_L ] };
+key <AB04> { [ v, V ] };
+key <INS> { [ Insert ] };
+key <BKSP> { [ BackSpace ] };
+modifier_map Control { <LCTL> };
+modifier_map Shift { <LFSH> };
+};
+};
+";
+
+/// Types text with a virtual keyboard, which sway, river, niri and most other
+/// wlroots-based compositors offer.
+pub(crate) struct VirtualKeyboardBackend;
+
+impl InsertBackend for VirtualKeyboardBackend {
+	fn kind(&self) -> BackendKind { BackendKind::VirtualKeyboard }
+
+	fn available(&self) -> bool { wayland_capabilities().virtual_keyboard }
+
+	fn insert(&self, text: &str, target: &InsertTarget) -> Result<Outcome, InsertError> {
+		wait_for_focus(target);
+		type_text(text)?;
+		Ok(Outcome::Inserted)
+	}
+}
+
+/// Waits for the compositor to hand keyboard focus to a window again now that
+/// the picker is gone, and to the captured one when its title is known.
+/// Compositors that do not report focus get the text straight away.
+fn wait_for_focus(target: &InsertTarget) {
+	let mut watch = match ActivationWatch::start() {
+		Ok(Some(watch)) => watch,
+		Ok(None) => {
+			debug!("compositor does not report focus; typing straight away");
+			return;
+		}
+		Err(e) => {
+			warn!("could not follow focus; typing straight away: {e}");
+			return;
+		}
+	};
+
+	let focused = watch.wait_for_activation(FOCUS_TIMEOUT, |title| {
+		target.title.as_deref().is_none_or(|wanted| wanted == title)
+	});
+	match focused {
+		Ok(true) => {}
+		Ok(false) => warn!("the target window did not report focus; typing anyway"),
+		Err(e) => debug!("lost track of focus: {e}"),
+	}
+}
+
+struct State;
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+	fn event(
+		_: &mut Self,
+		_: &wl_registry::WlRegistry,
+		_: wl_registry::Event,
+		_: &GlobalListContents,
+		_: &Connection,
+		_: &QueueHandle<Self>,
+	) {
+	}
+}
+
+delegate_noop!(State: ignore wl_seat::WlSeat);
+delegate_noop!(State: ZwpVirtualKeyboardManagerV1);
+delegate_noop!(State: ZwpVirtualKeyboardV1);
+
+fn failed(context: &str, e: impl std::fmt::Display) -> InsertError {
+	InsertError::Failed(format!("{context}: {e}"))
+}
+
+fn create_keyboard() -> Result<(Connection, EventQueue<State>, ZwpVirtualKeyboardV1), InsertError> {
+	let connection =
+		Connection::connect_to_env().map_err(|e| failed("could not connect to Wayland", e))?;
+	let (globals, queue) = registry_queue_init::<State>(&connection)
+		.map_err(|e| failed("could not read Wayland globals", e))?;
+	let qh = queue.handle();
+
+	let seat: wl_seat::WlSeat =
+		globals.bind(&qh, 1..=1, ()).map_err(|e| failed("no Wayland seat", e))?;
+	let manager: ZwpVirtualKeyboardManagerV1 =
+		globals.bind(&qh, 1..=1, ()).map_err(|e| failed("virtual keyboard unsupported", e))?;
+	let keyboard = manager.create_virtual_keyboard(&seat, &qh, ());
+
+	Ok((connection, queue, keyboard))
+}
+
+fn set_keymap(
+	keyboard: &ZwpVirtualKeyboardV1,
+	queue: &mut EventQueue<State>,
+	keymap: &CStr,
+) -> Result<(), InsertError> {
+	let keymap_file = keymap_file(keymap).map_err(|e| failed("could not create keymap", e))?;
+	keyboard.keymap(
+		KEYMAP_FORMAT_XKB_V1,
+		keymap_file.as_fd(),
+		keymap.to_bytes_with_nul().len() as u32,
+	);
+	queue.roundtrip(&mut State).map_err(|e| failed("keymap was not accepted", e))?;
+	Ok(())
+}
+
+fn now_millis() -> u32 {
+	SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u32
+}
+
+pub(crate) fn type_text(text: &str) -> Result<(), InsertError> {
+	let (connection, mut queue, keyboard) = create_keyboard()?;
+
+	let mut code_points: Vec<char> = Vec::new();
+	for ch in text.chars() {
+		if !code_points.contains(&ch) {
+			code_points.push(ch);
+		}
+	}
+
+	set_keymap(&keyboard, &mut queue, &keymap_for(&code_points))?;
+
+	let mut time = now_millis();
+	for ch in text.chars() {
+		let index = code_points.iter().position(|c| *c == ch).unwrap_or_default() as u32;
+		let evdev_code = index + 1;
+		keyboard.key(time, evdev_code, 1);
+		keyboard.key(time.wrapping_add(1), evdev_code, 0);
+		time = time.wrapping_add(2);
+	}
+
+	queue.roundtrip(&mut State).map_err(|e| failed("key events were not delivered", e))?;
+	keyboard.destroy();
+	connection.flush().map_err(|e| failed("could not flush Wayland connection", e))?;
+	Ok(())
+}
+
+/// Presses a paste shortcut on a virtual keyboard with a keymap holding just
+/// the keys involved.
+pub(crate) fn press_shortcut(shortcut: PasteShortcut) -> Result<(), InsertError> {
+	let (connection, mut queue, keyboard) = create_keyboard()?;
+	set_keymap(&keyboard, &mut queue, SHORTCUT_KEYMAP)?;
+
+	let (modifiers, key) = shortcut_keys(shortcut);
+	let mut depressed = 0;
+	let mut time = now_millis();
+	for modifier in modifiers {
+		keyboard.key(time, (*modifier).into(), 1);
+		depressed |= modifier_mask(*modifier);
+		keyboard.modifiers(depressed, 0, 0, 0);
+		time = time.wrapping_add(1);
+	}
+	keyboard.key(time, key.into(), 1);
+	keyboard.key(time.wrapping_add(1), key.into(), 0);
+	time = time.wrapping_add(2);
+	for modifier in modifiers.iter().rev() {
+		keyboard.key(time, (*modifier).into(), 0);
+		depressed &= !modifier_mask(*modifier);
+		keyboard.modifiers(depressed, 0, 0, 0);
+		time = time.wrapping_add(1);
+	}
+
+	queue.roundtrip(&mut State).map_err(|e| failed("key events were not delivered", e))?;
+	keyboard.destroy();
+	connection.flush().map_err(|e| failed("could not flush Wayland connection", e))?;
+	Ok(())
+}
+
+/// Presses BackSpace `count` times.
+pub(crate) fn press_backspace(count: usize) -> Result<(), InsertError> {
+	let (connection, mut queue, keyboard) = create_keyboard()?;
+	set_keymap(&keyboard, &mut queue, SHORTCUT_KEYMAP)?;
+
+	let mut time = now_millis();
+	for _ in 0..count {
+		keyboard.key(time, KEY_BACKSPACE.into(), 1);
+		keyboard.key(time.wrapping_add(1), KEY_BACKSPACE.into(), 0);
+		time = time.wrapping_add(2);
+	}
+
+	queue.roundtrip(&mut State).map_err(|e| failed("key events were not delivered", e))?;
+	keyboard.destroy();
+	connection.flush().map_err(|e| failed("could not flush Wayland connection", e))?;
+	Ok(())
+}
+
+/// The xkb modifier mask a modifier key sets in [`SHORTCUT_KEYMAP`]; the
+/// virtual keyboard protocol leaves tracking modifier state to the client.
+fn modifier_mask(key: u16) -> u32 {
+	match key {
+		KEY_LEFTSHIFT => 1 << 0,
+		KEY_LEFTCTRL => 1 << 2,
+		_ => 0,
+	}
+}
+
+/// Builds a keymap where evdev keycode `n + 1` produces the `n`th code point.
+fn keymap_for(code_points: &[char]) -> CString {
+	let first = 1 + XKB_KEYCODE_OFFSET;
+	let last = (code_points.len() as u32 + XKB_KEYCODE_OFFSET).max(first);
+
+	let mut keymap = String::from("xkb_keymap {\n");
+	keymap.push_str(&format!("xkb_keycodes \"bob\" {{\nminimum = 8;\nmaximum = {last};\n"));
+	for index in 0..code_points.len() as u32 {
+		let keycode = first + index;
+		keymap.push_str(&format!("<K{keycode}> = {keycode};\n"));
+	}
+	keymap.push_str("};\n");
+	keymap.push_str("xkb_types \"bob\" { include \"complete\" };\n");
+	keymap.push_str("xkb_compatibility \"bob\" { include \"complete\" };\n");
+	keymap.push_str("xkb_symbols \"bob\" {\n");
+	for (index, ch) in code_points.iter().enumerate() {
+		let keycode = first + index as u32;
+		keymap.push_str(&format!("key <K{keycode}> {{ [ U{:04X} ] }};\n", *ch as u32));
+	}
+	keymap.push_str("};\n};\n");
+
+	CString::new(keymap).unwrap_or_default()
+}
+
+/// Puts the keymap in an anonymous memory file the compositor can map.
+fn keymap_file(keymap: &CStr) -> std::io::Result<File> {
+	let name = c"bob-keymap";
+	let fd = unsafe { libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC) };
+	if fd < 0 {
+		return Err(std::io::Error::last_os_error());
+	}
+
+	let mut file = unsafe { File::from_raw_fd(fd) };
+	file.write_all(keymap.to_bytes_with_nul())?;
+	Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_support::{PrivateCompositor, ScopedEnv, lock_env};
+
+	#[test]
+	fn keymap_binds_one_keycode_per_code_point() {
+		let keymap = keymap_for(&['a', '👍', '\u{200d}']);
+		let keymap = keymap.to_str().unwrap();
+
+		assert!(keymap.contains("minimum = 8;\nmaximum = 11;\n"));
+		for line in [
+			"<K9> = 9;",
+			"<K11> = 11;",
+			"key <K9> { [ U0061 ] };",
+			"key <K10> { [ U1F44D ] };",
+			"key <K11> { [ U200D ] };",
+		] {
+			assert!(keymap.contains(line), "{line} missing from\n{keymap}");
+		}
+		assert!(!keymap.contains("<K12>"));
+	}
+
+	#[test]
+	fn empty_text_still_gets_a_valid_keycode_range() {
+		let keymap = keymap_for(&[]);
+		let keymap = keymap.to_str().unwrap();
+
+		assert!(keymap.contains("minimum = 8;\nmaximum = 9;\n"));
+		assert!(!keymap.contains("key <"));
+	}
+
+	#[test]
+	fn modifier_masks_match_the_shortcut_keymap() {
+		assert_eq!(modifier_mask(KEY_LEFTSHIFT), 1 << 0);
+		assert_eq!(modifier_mask(KEY_LEFTCTRL), 1 << 2);
+		assert_eq!(modifier_mask(KEY_BACKSPACE), 0);
+	}
+
+	#[test]
+	fn a_headless_compositor_takes_the_keymaps_and_keys() {
+		let _env = lock_env();
+		let Some(compositor) = PrivateCompositor::start() else {
+			return;
+		};
+		let mut env = ScopedEnv::default();
+		env.set("WAYLAND_DISPLAY", &compositor.socket);
+
+		type_text("👍🏽 a👍").expect("typing is accepted");
+		for shortcut in [PasteShortcut::CtrlV, PasteShortcut::CtrlShiftV, PasteShortcut::ShiftInsert] {
+			press_shortcut(shortcut).expect("shortcut is accepted");
+		}
+		press_backspace(3).expect("backspace is accepted");
+	}
+}
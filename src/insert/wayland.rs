@@ -1,90 +1,400 @@
 //! Wayland clipboard management and Hyprland-specific emoji paste insertion.
-use std::{thread, time::Duration};
-
-use hyprland::dispatch::{Dispatch, DispatchType};
-use tracing::{error, warn};
-
-use crate::integration::linux::{PendingInsertTarget, SHIFT_PASTE_CLASSES};
-
-pub(crate) fn wl_copy(text: &str) -> std::io::Result<()> {
-	use wl_clipboard_rs::copy::{MimeType, Options, Source};
-	let opts = Options::new();
-	opts
-		.copy(
-			Source::Bytes(text.as_bytes().to_vec().into()),
-			MimeType::Specific("text/plain".to_string()),
-		)
-		.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+use std::{
+	io::{self, Read, Write},
+	process::{Command, Stdio},
+	thread,
+	time::{Duration, Instant},
+};
+
+use tracing::{debug, warn};
+
+use super::{
+	backend::{BackendKind, InsertBackend, InsertError, InsertTarget, Outcome},
+	clipboard_source,
+};
+use crate::{
+	config::CONFIG,
+	insert::rules,
+	integration::{
+		hyprland_ipc,
+		linux::{CLIPBOARD_MANAGER_HINT, LinuxSession, PendingInsertTarget, detect_linux_session},
+	},
+};
+
+/// Copies `text` as `text/plain`. A `transient` copy is tagged so clipboard
+/// managers do not record it.
+pub(crate) fn wl_copy(text: &str, transient: bool) -> std::io::Result<()> {
+	use wl_clipboard_rs::copy::{MimeSource, MimeType, Options, Source};
+
+	let mut sources = vec![MimeSource {
+		source:    Source::Bytes(text.as_bytes().to_vec().into()),
+		mime_type: MimeType::Specific("text/plain".to_string()),
+	}];
+	if transient {
+		sources.push(MimeSource {
+			source:    Source::Bytes(b"secret".to_vec().into()),
+			mime_type: MimeType::Specific(CLIPBOARD_MANAGER_HINT.to_string()),
+		});
+	}
+
+	Options::new().copy_multi(sources).map_err(std::io::Error::other)
 }
 
-fn wl_paste() -> std::io::Result<Option<String>> {
-	use std::io::Read;
+/// Makes `text` the primary selection, so a middle-click pastes it.
+pub(crate) fn wl_copy_primary(text: &str) -> std::io::Result<()> {
+	use wl_clipboard_rs::copy::{ClipboardType, MimeType, Options, Source};
 
+	let mut options = Options::new();
+	options.clipboard(ClipboardType::Primary);
+	options
+		.copy(Source::Bytes(text.as_bytes().to_vec().into()), MimeType::Text)
+		.map_err(std::io::Error::other)
+}
+
+/// Reads the primary selection, i.e. the text currently selected. `None` when
+/// nothing is selected or the selection holds no text.
+pub(crate) fn wl_read_primary() -> std::io::Result<Option<String>> {
 	use wl_clipboard_rs::paste::{ClipboardType, Error, MimeType, Seat, get_contents};
 
-	let result = get_contents(ClipboardType::Regular, Seat::Unspecified, MimeType::Text);
-	match result {
-		Ok((mut pipe, _)) => {
-			let mut contents = vec![];
-			pipe.read_to_end(&mut contents)?;
-			let text = String::from_utf8_lossy(&contents).into_owned();
-			if text.trim().is_empty() { Ok(None) } else { Ok(Some(text)) }
+	let pipe = match get_contents(ClipboardType::Primary, Seat::Unspecified, MimeType::Text) {
+		Ok((pipe, _)) => pipe,
+		Err(Error::NoSeats) | Err(Error::ClipboardEmpty) | Err(Error::NoMimeType) => return Ok(None),
+		Err(e) => return Err(std::io::Error::other(e)),
+	};
+
+	let mut contents = Vec::new();
+	pipe.take(MAX_PRIMARY_READ as u64).read_to_end(&mut contents)?;
+	Ok(Some(String::from_utf8_lossy(&contents).into_owned()))
+}
+
+/// Upper bound on how much of the primary selection is read.
+const MAX_PRIMARY_READ: usize = 4096;
+
+/// How long the target gets to ask for the pasted text before the previous
+/// clipboard is put back regardless.
+pub(crate) const PASTE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Upper bound on how much of the previous clipboard is kept for restoring.
+/// Offers past it are dropped rather than held in memory.
+const MAX_SAVED_CLIPBOARD: usize = 64 * 1024 * 1024;
+
+/// Runs the process as the holder of a restored clipboard instead of the
+/// picker; see [`wl_restore`].
+pub(crate) const SERVE_CLIPBOARD_FLAG: &str = "--serve-wayland-clipboard";
+
+/// A clipboard selection captured in every MIME type it was offered in, so it
+/// can be put back byte-for-byte.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct SavedClipboard {
+	offers: Vec<(String, Vec<u8>)>,
+}
+
+impl SavedClipboard {
+	/// Writes the offers for a clipboard holder.
+	fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+		for (mime_type, contents) in &self.offers {
+			out.write_all(&(mime_type.len() as u32).to_ne_bytes())?;
+			out.write_all(mime_type.as_bytes())?;
+			out.write_all(&(contents.len() as u32).to_ne_bytes())?;
+			out.write_all(contents)?;
+		}
+		Ok(())
+	}
+
+	/// Reads what [`write_to`](Self::write_to) wrote.
+	fn read_from(input: &mut impl Read) -> io::Result<Self> {
+		fn chunk(input: &mut impl Read, length: [u8; 4]) -> io::Result<Vec<u8>> {
+			let mut chunk = vec![0; u32::from_ne_bytes(length) as usize];
+			input.read_exact(&mut chunk)?;
+			Ok(chunk)
+		}
+
+		let mut saved = Self::default();
+		let mut length = [0; 4];
+		loop {
+			match input.read_exact(&mut length) {
+				Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(saved),
+				read => read?,
+			}
+			let mime_type = String::from_utf8(chunk(input, length)?)
+				.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+			input.read_exact(&mut length)?;
+			saved.offers.push((mime_type, chunk(input, length)?));
+		}
+	}
+}
+
+/// Where a clipboard is saved from: the Wayland clipboard outside tests.
+trait ClipboardReader {
+	/// The MIME types on offer; `None` when the clipboard is empty.
+	fn mime_types(&self) -> io::Result<Option<Vec<String>>>;
+
+	/// The contents in one MIME type; `None` when it is no longer offered.
+	fn contents(&self, mime_type: &str) -> io::Result<Option<Box<dyn Read>>>;
+}
+
+struct WaylandClipboard;
+
+impl ClipboardReader for WaylandClipboard {
+	fn mime_types(&self) -> io::Result<Option<Vec<String>>> {
+		use wl_clipboard_rs::paste::{ClipboardType, Error, Seat, get_mime_types_ordered};
+
+		match get_mime_types_ordered(ClipboardType::Regular, Seat::Unspecified) {
+			Ok(mime_types) => Ok(Some(mime_types)),
+			Err(Error::NoSeats) | Err(Error::ClipboardEmpty) => Ok(None),
+			Err(e) => Err(io::Error::other(e)),
+		}
+	}
+
+	fn contents(&self, mime_type: &str) -> io::Result<Option<Box<dyn Read>>> {
+		use wl_clipboard_rs::paste::{ClipboardType, Error, MimeType, Seat, get_contents};
+
+		match get_contents(ClipboardType::Regular, Seat::Unspecified, MimeType::Specific(mime_type)) {
+			Ok((pipe, _)) => Ok(Some(Box::new(pipe))),
+			// The selection changed while it was being read.
+			Err(Error::ClipboardEmpty) | Err(Error::NoMimeType) => Ok(None),
+			Err(e) => Err(io::Error::other(e)),
+		}
+	}
+}
+
+fn wl_save() -> io::Result<SavedClipboard> { save_from(&WaylandClipboard) }
+
+fn save_from(clipboard: &impl ClipboardReader) -> io::Result<SavedClipboard> {
+	let Some(mime_types) = clipboard.mime_types()? else {
+		return Ok(SavedClipboard::default());
+	};
+
+	let mut saved = SavedClipboard::default();
+	let mut total = 0;
+	for mime_type in mime_types {
+		let Some(pipe) = clipboard.contents(&mime_type)? else {
+			continue;
+		};
+
+		let mut contents = Vec::new();
+		let limit = (MAX_SAVED_CLIPBOARD - total) as u64;
+		pipe.take(limit + 1).read_to_end(&mut contents)?;
+		if contents.len() as u64 > limit {
+			warn!(%mime_type, "clipboard offer too large to save; it will not be restored");
+			continue;
 		}
-		Err(Error::NoSeats) | Err(Error::ClipboardEmpty) | Err(Error::NoMimeType) => Ok(None),
-		Err(e) => Err(std::io::Error::new(std::io::ErrorKind::Other, e)),
+
+		total += contents.len();
+		saved.offers.push((mime_type, contents));
 	}
+
+	Ok(saved)
 }
 
-pub(crate) fn insert_hyprland(emoji: &str, target: Option<&PendingInsertTarget>) {
-	use std::process::Command;
+/// Puts a saved clipboard back. A Wayland selection only lives as long as the
+/// client serving it, which has to outlast a picker that quits after
+/// inserting, so a detached copy of the executable serves it.
+fn wl_restore(saved: SavedClipboard) -> io::Result<()> {
+	use wl_clipboard_rs::copy::{self, ClipboardType, Seat};
 
-	let Some(target) = target else {
-		warn!("no Hyprland insert target captured; falling back to clipboard copy");
-		let _ = wl_copy(emoji);
-		return;
+	if saved.offers.is_empty() {
+		return copy::clear(ClipboardType::Regular, Seat::All).map_err(io::Error::other);
+	}
+
+	let mut holder = Command::new(std::env::current_exe()?)
+		.arg(SERVE_CLIPBOARD_FLAG)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::null())
+		.stderr(Stdio::null())
+		.spawn()?;
+	let written = match holder.stdin.take() {
+		Some(mut stdin) => saved.write_to(&mut stdin),
+		None => Err(io::Error::other("clipboard holder has no stdin")),
 	};
+	thread::spawn(move || holder.wait());
+	written
+}
+
+/// Entry point for [`SERVE_CLIPBOARD_FLAG`]: serves the clipboard read from
+/// stdin until another client replaces it.
+pub(crate) fn serve_clipboard_from_stdin() {
+	use wl_clipboard_rs::copy::{MimeSource, MimeType, Options, Source};
+
+	let saved = match SavedClipboard::read_from(&mut io::stdin().lock()) {
+		Ok(saved) => saved,
+		Err(e) => {
+			warn!("failed to read clipboard contents to hold: {e}");
+			return;
+		}
+	};
+
+	let sources = saved
+		.offers
+		.into_iter()
+		.map(|(mime_type, contents)| MimeSource {
+			source:    Source::Bytes(contents.into()),
+			mime_type: MimeType::Specific(mime_type),
+		})
+		.collect();
+	let mut options = Options::new();
+	options.foreground(true);
+	if let Err(e) = options.copy_multi(sources) {
+		warn!("failed to hold the restored clipboard: {e}");
+	}
+}
+
+/// Pastes into the captured window with Hyprland's `sendshortcut` dispatcher,
+/// so focus does not need to come back first.
+pub(crate) struct HyprlandPasteBackend;
+
+impl InsertBackend for HyprlandPasteBackend {
+	fn kind(&self) -> BackendKind { BackendKind::HyprlandPaste }
+
+	fn available(&self) -> bool { detect_linux_session() == LinuxSession::WaylandHyprland }
 
+	fn insert(&self, text: &str, target: &InsertTarget) -> Result<Outcome, InsertError> {
+		insert_hyprland(text, target)?;
+		Ok(Outcome::Inserted)
+	}
+}
+
+pub(crate) fn insert_hyprland(
+	emoji: &str,
+	target: &PendingInsertTarget,
+) -> Result<(), InsertError> {
 	let Some(address) = target.hyprland_address.as_deref() else {
-		warn!("no Hyprland window address; falling back to clipboard copy");
-		let _ = wl_copy(emoji);
-		return;
+		return Err(InsertError::Unsupported("no Hyprland window address was captured".into()));
 	};
 
-	let original_clipboard = wl_paste().ok().flatten();
+	let workspace = hyprland_ipc::window_workspace(address)
+		.map_err(|e| InsertError::Failed(format!("could not list Hyprland windows: {e}")))?
+		.ok_or(InsertError::TargetClosed)?;
+	if hyprland_ipc::is_special_workspace(&workspace) {
+		hyprland_ipc::reveal_special_workspace(&workspace).map_err(|e| {
+			InsertError::Failed(format!("hyprland dispatch togglespecialworkspace failed: {e}"))
+		})?;
+	}
 
-	if let Err(e) = wl_copy(emoji) {
-		error!("wl-copy failed: {e}");
-		return;
+	// The shortcut reaches the window wherever it is, but the user should see
+	// where the text went.
+	hyprland_ipc::focus_window(address)
+		.map_err(|e| InsertError::Failed(format!("hyprland dispatch focuswindow failed: {e}")))?;
+	match hyprland_ipc::wait_for_focus(address, super::FOCUS_TIMEOUT) {
+		Ok(true) => {}
+		Ok(false) => debug!(address, "target window did not report focus; pasting anyway"),
+		Err(e) => debug!(address, "could not follow Hyprland focus; pasting anyway: {e}"),
 	}
 
-	const COPY_DELAY: Duration = Duration::from_millis(25);
+	let rule = rules::resolve(&CONFIG.rules, target.class.as_deref(), target.title.as_deref());
+	let (mods, key) = rule.paste.hyprland_keys();
 
-	thread::sleep(COPY_DELAY);
+	paste_via_clipboard(emoji, || {
+		hyprland_ipc::send_shortcut(mods, key, address)
+			.map_err(|e| InsertError::Failed(format!("hyprland dispatch sendshortcut failed: {e}")))
+	})
+}
 
-	let needs_shift = target
-		.class
-		.as_deref()
-		.map(|c| {
-			let lower = c.to_lowercase();
-			SHIFT_PASTE_CLASSES.iter().any(|t| lower.contains(t))
-		})
-		.unwrap_or(false);
+/// Puts `text` on the clipboard, runs `paste` to have the target paste it, and
+/// once the target has read it puts back whatever the clipboard held before, in
+/// every format it held.
+pub(crate) fn paste_via_clipboard(
+	text: &str,
+	paste: impl FnOnce() -> Result<(), InsertError>,
+) -> Result<(), InsertError> {
+	let original_clipboard =
+		wl_save().inspect_err(|e| warn!("failed to save clipboard; it will not be restored: {e}")).ok();
 
-	let (mods, key) = if needs_shift { ("CONTROL SHIFT", "V") } else { ("CONTROL", "V") };
+	let source = clipboard_source::offer(text, true)?;
 
-	let args = format!("{mods} {key} address:{address}");
+	let shortcut_sent = Instant::now();
+	let pasted = paste();
+	if pasted.is_ok() && !source.wait_for_request_since(shortcut_sent, PASTE_TIMEOUT) {
+		warn!("the target never asked for the pasted text");
+	}
 
-	if let Err(e) = Dispatch::call(DispatchType::Custom("sendshortcut", &args)) {
-		error!("hyprland dispatch sendshortcut failed: {e}");
+	if let Some(original) = original_clipboard
+		&& let Err(e) = wl_restore(original)
+	{
+		warn!("failed to restore clipboard: {e}");
 	}
 
-	const PASTE_DELAY: Duration = Duration::from_millis(50);
+	pasted
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	type Contents = fn() -> Box<dyn Read>;
 
-	thread::sleep(PASTE_DELAY);
+	/// A clipboard that offers each MIME type with the bytes its `Contents`
+	/// makes.
+	struct FakeClipboard {
+		offers: Vec<(&'static str, Contents)>,
+	}
 
-	if let Some(original) = original_clipboard {
-		if let Err(e) = wl_copy(&original) {
-			warn!("failed to restore clipboard: {e}");
+	impl ClipboardReader for FakeClipboard {
+		fn mime_types(&self) -> io::Result<Option<Vec<String>>> {
+			Ok(
+				(!self.offers.is_empty())
+					.then(|| self.offers.iter().map(|(mime_type, _)| mime_type.to_string()).collect()),
+			)
 		}
+
+		fn contents(&self, mime_type: &str) -> io::Result<Option<Box<dyn Read>>> {
+			Ok(self.offers.iter().find(|(offered, _)| *offered == mime_type).map(|(_, read)| read()))
+		}
+	}
+
+	fn offers(saved: &SavedClipboard) -> Vec<(&str, usize)> {
+		saved.offers.iter().map(|(mime_type, contents)| (mime_type.as_str(), contents.len())).collect()
+	}
+
+	#[test]
+	fn an_empty_clipboard_saves_nothing() {
+		let saved = save_from(&FakeClipboard { offers: Vec::new() }).unwrap();
+		assert_eq!(saved, SavedClipboard::default());
+	}
+
+	#[test]
+	fn every_mime_type_survives_the_trip_to_the_holder() {
+		let clipboard = FakeClipboard {
+			offers: vec![
+				("text/html", || Box::new(&b"<b>\xF0\x9F\xA6\x80</b>"[..])),
+				("text/plain;charset=utf-8", || Box::new("🦀".as_bytes())),
+				("image/png", || Box::new(&[0x89, b'P', b'N', b'G', 0, 0xff][..])),
+			],
+		};
+		let saved = save_from(&clipboard).unwrap();
+		assert_eq!(offers(&saved), [
+			("text/html", 11),
+			("text/plain;charset=utf-8", 4),
+			("image/png", 6),
+		]);
+
+		let mut holder_input = Vec::new();
+		saved.write_to(&mut holder_input).unwrap();
+		assert_eq!(SavedClipboard::read_from(&mut holder_input.as_slice()).unwrap(), saved);
+	}
+
+	#[test]
+	fn offers_past_the_cap_are_dropped() {
+		let clipboard = FakeClipboard {
+			offers: vec![
+				("text/plain", || Box::new(&b"crab"[..])),
+				// One byte more than the text leaves of the cap.
+				("image/png", || Box::new(io::repeat(0).take((MAX_SAVED_CLIPBOARD - 3) as u64))),
+				("text/html", || Box::new(&b"<i>crab</i>"[..])),
+			],
+		};
+
+		let saved = save_from(&clipboard).unwrap();
+		assert_eq!(offers(&saved), [("text/plain", 4), ("text/html", 11)]);
+	}
+
+	#[test]
+	fn an_offer_that_fills_the_cap_is_kept() {
+		let clipboard = FakeClipboard {
+			offers: vec![("image/png", || Box::new(io::repeat(0).take(MAX_SAVED_CLIPBOARD as u64)))],
+		};
+
+		let saved = save_from(&clipboard).unwrap();
+		assert_eq!(offers(&saved), [("image/png", MAX_SAVED_CLIPBOARD)]);
 	}
 }
@@ -0,0 +1,84 @@
+//! Paste insertion on X11 for applications that mangle emoji typed through
+//! XTest: the text goes on the CLIPBOARD selection and the paste shortcut is
+//! pressed instead.
+
+use std::time::Duration;
+
+use tracing::warn;
+
+use super::{
+	automated::{press_shortcut_enigo, restore_focus},
+	backend::{BackendKind, InsertBackend, InsertError, InsertTarget, Outcome},
+	rules::{self, PasteShortcut},
+};
+use crate::{
+	config::CONFIG,
+	integration::{
+		linux::{LinuxSession, detect_linux_session},
+		x11,
+	},
+};
+
+/// How long the target gets to ask for the pasted text before the previous
+/// clipboard is put back.
+const PASTE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Pastes into windows whose rule prefers it, leaving typing to the enigo
+/// backend everywhere else.
+pub(crate) struct X11PasteBackend;
+
+impl InsertBackend for X11PasteBackend {
+	fn kind(&self) -> BackendKind { BackendKind::X11Paste }
+
+	fn available(&self) -> bool { detect_linux_session() == LinuxSession::X11 }
+
+	fn insert(&self, text: &str, target: &InsertTarget) -> Result<Outcome, InsertError> {
+		let rule = rules::resolve(&CONFIG.rules, target.class.as_deref(), target.title.as_deref());
+		if !rule.prefer_paste {
+			return Err(InsertError::Unsupported("the target window is typed into instead".into()));
+		}
+
+		restore_focus(target)?;
+		paste_x11(text, rule.paste)?;
+		Ok(Outcome::Inserted)
+	}
+}
+
+/// Pastes `text` into the focused window through the CLIPBOARD, then puts back
+/// what the clipboard held before in every target it held. A clipboard that
+/// could not be saved is left holding `text` rather than cleared.
+pub(crate) fn paste_x11(text: &str, shortcut: PasteShortcut) -> Result<(), InsertError> {
+	paste_via_x11_clipboard(text, || press_shortcut_enigo(shortcut))
+}
+
+/// Puts `text` on the CLIPBOARD, runs `paste` to have the target paste it, and
+/// once the target has read it puts back what the clipboard held before.
+/// Through XWayland this also reaches Wayland windows on compositors that
+/// mirror the X11 clipboard, as GNOME does.
+pub(crate) fn paste_via_x11_clipboard(
+	text: &str,
+	paste: impl FnOnce() -> Result<(), InsertError>,
+) -> Result<(), InsertError> {
+	let previous = x11::save_clipboard()
+		.inspect_err(|e| warn!("failed to save the X11 clipboard; it will not be restored: {e}"))
+		.ok();
+	let served = x11::own_clipboard(text.as_bytes().to_vec())
+		.map_err(|e| InsertError::Failed(format!("could not copy to the X11 clipboard: {e}")))?;
+
+	// Clipboard managers may have fetched the new selection already; only a
+	// request after the shortcut counts as the paste.
+	while served.try_recv().is_ok() {}
+
+	let pasted = paste();
+	if pasted.is_ok() && served.recv_timeout(PASTE_TIMEOUT).is_err() {
+		warn!("the target window never asked for the pasted text");
+	}
+
+	if let Some(previous) = previous
+		&& let Err(e) = x11::restore_clipboard(previous)
+	{
+		warn!("failed to restore the X11 clipboard: {e}");
+	}
+
+	pasted
+}
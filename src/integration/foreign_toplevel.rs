@@ -0,0 +1,147 @@
+//! Keyboard focus as wlroots-based compositors report it through
+//! `zwlr_foreign_toplevel_manager_v1`, for Wayland sessions without an IPC
+//! socket to ask instead.
+
+use std::{
+	collections::HashMap,
+	io,
+	time::{Duration, Instant},
+};
+
+use wayland_client::{
+	Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+	backend::ObjectId,
+	event_created_child,
+	globals::{GlobalListContents, registry_queue_init},
+	protocol::wl_registry,
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+	zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+	zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+
+use super::linux::{dispatch_within, wayland_advertises};
+
+const MANAGER_INTERFACE: &str = "zwlr_foreign_toplevel_manager_v1";
+
+/// `zwlr_foreign_toplevel_handle_v1.state.activated`.
+const STATE_ACTIVATED: u32 = 2;
+
+#[derive(Default)]
+struct Toplevel {
+	title:     String,
+	activated: bool,
+}
+
+#[derive(Default)]
+struct State {
+	toplevels: HashMap<ObjectId, Toplevel>,
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+	fn event(
+		_: &mut Self,
+		_: &wl_registry::WlRegistry,
+		_: wl_registry::Event,
+		_: &GlobalListContents,
+		_: &Connection,
+		_: &QueueHandle<Self>,
+	) {
+	}
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for State {
+	event_created_child!(State, ZwlrForeignToplevelManagerV1, [
+		zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+	]);
+
+	fn event(
+		state: &mut Self,
+		_: &ZwlrForeignToplevelManagerV1,
+		event: zwlr_foreign_toplevel_manager_v1::Event,
+		_: &(),
+		_: &Connection,
+		_: &QueueHandle<Self>,
+	) {
+		if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+			state.toplevels.insert(toplevel.id(), Toplevel::default());
+		}
+	}
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for State {
+	fn event(
+		state: &mut Self,
+		handle: &ZwlrForeignToplevelHandleV1,
+		event: zwlr_foreign_toplevel_handle_v1::Event,
+		_: &(),
+		_: &Connection,
+		_: &QueueHandle<Self>,
+	) {
+		let toplevel = state.toplevels.entry(handle.id()).or_default();
+		match event {
+			zwlr_foreign_toplevel_handle_v1::Event::Title { title } => toplevel.title = title,
+			// An array of native-endian `u32` states.
+			zwlr_foreign_toplevel_handle_v1::Event::State { state: states } => {
+				toplevel.activated = states.chunks_exact(4).any(|chunk| {
+					u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) == STATE_ACTIVATED
+				});
+			}
+			zwlr_foreign_toplevel_handle_v1::Event::Closed => {
+				state.toplevels.remove(&handle.id());
+				handle.destroy();
+			}
+			_ => {}
+		}
+	}
+}
+
+/// Follows which toplevel the compositor has activated.
+pub(crate) struct ActivationWatch {
+	queue: EventQueue<State>,
+	state: State,
+}
+
+impl ActivationWatch {
+	/// Starts following activation; `None` when the compositor does not report
+	/// it.
+	pub(crate) fn start() -> io::Result<Option<Self>> {
+		if !wayland_advertises(MANAGER_INTERFACE) {
+			return Ok(None);
+		}
+
+		let connection = Connection::connect_to_env().map_err(io::Error::other)?;
+		let (globals, queue) = registry_queue_init::<State>(&connection).map_err(io::Error::other)?;
+		let _: ZwlrForeignToplevelManagerV1 =
+			globals.bind(&queue.handle(), 1..=3, ()).map_err(io::Error::other)?;
+		Ok(Some(Self { queue, state: State::default() }))
+	}
+
+	/// Waits for the compositor to activate a toplevel whose title `wanted`
+	/// accepts, following the events it sends as activation moves; `false` on
+	/// timeout.
+	pub(crate) fn wait_for_activation(
+		&mut self,
+		timeout: Duration,
+		mut wanted: impl FnMut(&str) -> bool,
+	) -> io::Result<bool> {
+		let deadline = Instant::now() + timeout;
+		self.queue.roundtrip(&mut self.state).map_err(io::Error::other)?;
+
+		loop {
+			if self.activated_title().is_some_and(&mut wanted) {
+				return Ok(true);
+			}
+			let left = deadline.saturating_duration_since(Instant::now());
+			if left.is_zero() {
+				return Ok(false);
+			}
+			dispatch_within(&mut self.queue, &mut self.state, left)?;
+		}
+	}
+
+	/// The title of the activated toplevel; `None` while nothing is activated.
+	fn activated_title(&self) -> Option<&str> {
+		self.state.toplevels.values().find(|toplevel| toplevel.activated).map(|t| t.title.as_str())
+	}
+}
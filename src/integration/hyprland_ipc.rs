@@ -0,0 +1,377 @@
+//! Focus tracking for Hyprland over its IPC socket.
+//!
+//! The `hyprland` crate is not used: its 0.3 releases look for the socket only
+//! under `/tmp/hypr`, which Hyprland 0.40 left, and its event listener runs
+//! until an error with no way to stop waiting after a timeout. The requests
+//! needed here are one line of text each, sent directly as for sway/i3 and
+//! niri, which also lets the tests answer them from a stand-in socket.
+
+use std::{
+	io::{self, BufRead, BufReader, Read, Write},
+	os::unix::net::UnixStream,
+	path::PathBuf,
+	time::{Duration, Instant},
+};
+
+use serde_json::Value;
+use tracing::{debug, warn};
+
+use super::linux::PendingInsertTarget;
+
+/// Prefix Hyprland gives the names of special (scratchpad) workspaces.
+const SPECIAL_WORKSPACE_PREFIX: &str = "special";
+
+/// The request socket of the running Hyprland instance. Hyprland 0.40 moved it
+/// from `/tmp/hypr` into the runtime directory; older releases are still found.
+fn socket_path() -> io::Result<PathBuf> {
+	let signature = std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").ok_or_else(|| {
+		io::Error::new(io::ErrorKind::NotFound, "HYPRLAND_INSTANCE_SIGNATURE is not set")
+	})?;
+
+	std::env::var_os("XDG_RUNTIME_DIR")
+		.map(PathBuf::from)
+		.into_iter()
+		.chain([PathBuf::from("/tmp")])
+		.map(|dir| dir.join("hypr").join(&signature).join(".socket.sock"))
+		.find(|path| path.exists())
+		.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Hyprland's socket was not found"))
+}
+
+/// Sends one request and reads the reply, which Hyprland ends by closing the
+/// connection.
+fn request(request: &str) -> io::Result<String> {
+	let mut stream = UnixStream::connect(socket_path()?)?;
+	stream.write_all(request.as_bytes())?;
+
+	let mut reply = String::new();
+	stream.read_to_string(&mut reply)?;
+	Ok(reply)
+}
+
+/// Runs a query that answers in JSON.
+fn query(command: &str) -> io::Result<Value> {
+	serde_json::from_str(&request(&format!("j/{command}"))?)
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Runs a dispatcher; Hyprland answers `ok` or says what went wrong.
+fn dispatch(dispatcher: &str, args: &str) -> io::Result<()> {
+	let reply = request(format!("dispatch {dispatcher} {args}").trim_end())?;
+	match reply.trim() {
+		"ok" => Ok(()),
+		reason => Err(io::Error::other(format!("{dispatcher}: {reason}"))),
+	}
+}
+
+pub(crate) fn capture_active_window() -> PendingInsertTarget {
+	// `{}` when no window has focus.
+	let client = match query("activewindow") {
+		Ok(client) if client["address"].is_string() => client,
+		Ok(_) => {
+			debug!("no Hyprland window has focus");
+			return PendingInsertTarget::default();
+		}
+		Err(e) => {
+			warn!("failed to ask Hyprland for the active window: {e}");
+			return PendingInsertTarget::default();
+		}
+	};
+
+	PendingInsertTarget {
+		hyprland_address: client["address"].as_str().map(String::from),
+		class: client["class"].as_str().map(String::from),
+		title: client["title"].as_str().map(String::from),
+		..Default::default()
+	}
+}
+
+/// Name of the workspace the captured window sits on; `None` once the window
+/// has been closed.
+pub(crate) fn window_workspace(address: &str) -> io::Result<Option<String>> {
+	let clients = query("clients")?;
+	Ok(
+		clients
+			.as_array()
+			.into_iter()
+			.flatten()
+			.find(|client| client["address"].as_str() == Some(address))
+			.map(|client| client["workspace"]["name"].as_str().unwrap_or_default().to_string()),
+	)
+}
+
+/// Address of the window that has focus right now.
+pub(crate) fn active_address() -> io::Result<Option<String>> {
+	Ok(query("activewindow")?["address"].as_str().map(String::from))
+}
+
+/// Waits for the window to have focus, following the `activewindowv2` events
+/// on Hyprland's event socket; `false` on timeout.
+pub(crate) fn wait_for_focus(address: &str, timeout: Duration) -> io::Result<bool> {
+	let deadline = Instant::now() + timeout;
+	let mut events =
+		BufReader::new(UnixStream::connect(socket_path()?.with_file_name(".socket2.sock"))?);
+
+	// Focus may have moved before the connection was made.
+	if active_address()?.as_deref() == Some(address) {
+		return Ok(true);
+	}
+
+	// Events name windows without the `0x` that queries give them.
+	let wanted = address.trim_start_matches("0x");
+	let mut line = String::new();
+	loop {
+		let left = deadline.saturating_duration_since(Instant::now());
+		if left.is_zero() {
+			return Ok(false);
+		}
+		events.get_ref().set_read_timeout(Some(left))?;
+		line.clear();
+		match events.read_line(&mut line) {
+			Ok(0) => {
+				return Err(io::Error::new(
+					io::ErrorKind::UnexpectedEof,
+					"Hyprland closed the event socket",
+				));
+			}
+			Ok(_) if line.trim_end().strip_prefix("activewindowv2>>") == Some(wanted) => {
+				return Ok(true);
+			}
+			Ok(_) => {}
+			Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+				return Ok(false);
+			}
+			Err(e) => return Err(e),
+		}
+	}
+}
+
+pub(crate) fn is_special_workspace(name: &str) -> bool {
+	name.starts_with(SPECIAL_WORKSPACE_PREFIX)
+}
+
+/// Shows a special workspace unless a monitor already shows it; toggling it
+/// then would hide it instead.
+pub(crate) fn reveal_special_workspace(name: &str) -> io::Result<()> {
+	let monitors = query("monitors")?;
+	let shown = monitors
+		.as_array()
+		.into_iter()
+		.flatten()
+		.any(|monitor| monitor["specialWorkspace"]["name"].as_str() == Some(name));
+	if shown {
+		return Ok(());
+	}
+
+	// `special:scratch` is toggled as `scratch`, the unnamed `special` with no
+	// argument.
+	let toggle_name = name
+		.strip_prefix(SPECIAL_WORKSPACE_PREFIX)
+		.map(|rest| rest.trim_start_matches(':'))
+		.unwrap_or(name);
+	dispatch("togglespecialworkspace", toggle_name)
+}
+
+/// Focuses the window, which also switches to its regular workspace.
+pub(crate) fn focus_window(address: &str) -> io::Result<()> {
+	dispatch("focuswindow", &format!("address:{address}"))
+}
+
+/// Sends a shortcut straight to a window, whether or not it has focus. `mods`
+/// and `key` are in the form `PasteShortcut::hyprland_keys` gives.
+pub(crate) fn send_shortcut(mods: &str, key: &str, address: &str) -> io::Result<()> {
+	dispatch("sendshortcut", &format!("{mods}, {key}, address:{address}"))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		os::unix::net::UnixListener,
+		sync::{Arc, Mutex},
+		thread,
+	};
+
+	use serde_json::json;
+
+	use super::*;
+	use crate::test_support::{lock_env, remove_env, set_env};
+
+	/// A stand-in Hyprland request socket that answers `j/` queries from
+	/// `replies` and every dispatcher with `ok`, and records every request. Its
+	/// event socket sends `events` to whoever connects.
+	struct FakeHyprland {
+		runtime_dir: PathBuf,
+		requests:    Arc<Mutex<Vec<String>>>,
+	}
+
+	impl FakeHyprland {
+		fn serve(name: &str, replies: Value) -> Self { Self::serve_with_events(name, replies, &[]) }
+
+		fn serve_with_events(name: &str, replies: Value, events: &[&str]) -> Self {
+			let runtime_dir =
+				std::env::temp_dir().join(format!("bob-hypr-{name}-{}", std::process::id()));
+			let socket_dir = runtime_dir.join("hypr").join(name);
+			let _ = std::fs::remove_dir_all(&runtime_dir);
+			std::fs::create_dir_all(&socket_dir).expect("fake runtime dir is created");
+			let listener =
+				UnixListener::bind(socket_dir.join(".socket.sock")).expect("fake socket binds");
+			let requests = Arc::new(Mutex::new(Vec::new()));
+
+			let event_listener =
+				UnixListener::bind(socket_dir.join(".socket2.sock")).expect("fake event socket binds");
+			let events: String = events.iter().map(|event| format!("{event}\n")).collect();
+			thread::spawn(move || {
+				// Listeners stay connected.
+				let mut streams = Vec::new();
+				for mut stream in event_listener.incoming().flatten() {
+					let _ = stream.write_all(events.as_bytes());
+					streams.push(stream);
+				}
+			});
+
+			let log = requests.clone();
+			thread::spawn(move || {
+				for mut stream in listener.incoming().flatten() {
+					let mut buffer = [0; 4096];
+					let Ok(length) = stream.read(&mut buffer) else { continue };
+					let request = String::from_utf8_lossy(&buffer[..length]).into_owned();
+					log.lock().unwrap().push(request.clone());
+
+					let reply = match request.strip_prefix("j/") {
+						Some(query) => replies[query].to_string(),
+						None => "ok".to_string(),
+					};
+					let _ = stream.write_all(reply.as_bytes());
+				}
+			});
+
+			set_env("XDG_RUNTIME_DIR", &runtime_dir);
+			set_env("HYPRLAND_INSTANCE_SIGNATURE", name);
+			Self { runtime_dir, requests }
+		}
+
+		/// Dispatch requests, leaving out queries.
+		fn dispatches(&self) -> Vec<String> {
+			self.requests.lock().unwrap().iter().filter(|r| !r.starts_with("j/")).cloned().collect()
+		}
+	}
+
+	impl Drop for FakeHyprland {
+		fn drop(&mut self) {
+			remove_env("HYPRLAND_INSTANCE_SIGNATURE");
+			remove_env("XDG_RUNTIME_DIR");
+			let _ = std::fs::remove_dir_all(&self.runtime_dir);
+		}
+	}
+
+	fn terminal() -> Value {
+		json!({
+			"address": "0x5f1c9a0",
+			"class": "kitty",
+			"title": "~/src",
+			"workspace": { "id": -98, "name": "special:scratch" },
+		})
+	}
+
+	#[test]
+	fn captures_the_active_window() {
+		let _env = lock_env();
+		let _hyprland = FakeHyprland::serve("capture", json!({ "activewindow": terminal() }));
+
+		let target = capture_active_window();
+		assert_eq!(target.hyprland_address.as_deref(), Some("0x5f1c9a0"));
+		assert_eq!(target.class.as_deref(), Some("kitty"));
+		assert_eq!(target.title.as_deref(), Some("~/src"));
+	}
+
+	#[test]
+	fn captures_nothing_without_an_active_window() {
+		let _env = lock_env();
+		let _hyprland = FakeHyprland::serve("empty", json!({ "activewindow": {} }));
+
+		assert!(capture_active_window().hyprland_address.is_none());
+	}
+
+	#[test]
+	fn a_closed_window_has_no_workspace() {
+		let _env = lock_env();
+		let _hyprland = FakeHyprland::serve("closed", json!({ "clients": [terminal()] }));
+
+		assert_eq!(window_workspace("0x5f1c9a0").unwrap().as_deref(), Some("special:scratch"));
+		assert_eq!(window_workspace("0xdead").unwrap(), None);
+	}
+
+	#[test]
+	fn focuses_and_pastes_by_address() {
+		let _env = lock_env();
+		let hyprland = FakeHyprland::serve("paste", json!({}));
+
+		focus_window("0x5f1c9a0").unwrap();
+		send_shortcut("CONTROL", "V", "0x5f1c9a0").unwrap();
+		assert_eq!(hyprland.dispatches(), [
+			"dispatch focuswindow address:0x5f1c9a0",
+			"dispatch sendshortcut CONTROL, V, address:0x5f1c9a0",
+		]);
+	}
+
+	#[test]
+	fn waits_for_the_focus_event() {
+		let _env = lock_env();
+		let _hyprland = FakeHyprland::serve_with_events("events", json!({ "activewindow": {} }), &[
+			"activewindow>>kitty,~/src",
+			"activewindowv2>>5f1c9a1",
+			"activewindowv2>>5f1c9a0",
+		]);
+
+		assert!(wait_for_focus("0x5f1c9a0", Duration::from_secs(1)).unwrap());
+	}
+
+	#[test]
+	fn focus_already_there_needs_no_event() {
+		let _env = lock_env();
+		let _hyprland = FakeHyprland::serve("focused", json!({ "activewindow": terminal() }));
+
+		assert!(wait_for_focus("0x5f1c9a0", Duration::from_secs(1)).unwrap());
+	}
+
+	#[test]
+	fn focus_elsewhere_times_out() {
+		let _env = lock_env();
+		let _hyprland = FakeHyprland::serve_with_events("elsewhere", json!({ "activewindow": {} }), &[
+			"activewindowv2>>5f1c9a1",
+		]);
+
+		assert!(!wait_for_focus("0x5f1c9a0", Duration::from_millis(50)).unwrap());
+	}
+
+	#[test]
+	fn reveals_a_hidden_special_workspace() {
+		let _env = lock_env();
+		let hyprland = FakeHyprland::serve(
+			"hidden",
+			json!({
+				"monitors": [{ "name": "DP-1", "specialWorkspace": { "id": 0, "name": "" } }],
+			}),
+		);
+
+		reveal_special_workspace("special:scratch").unwrap();
+		reveal_special_workspace("special").unwrap();
+		assert_eq!(hyprland.dispatches(), [
+			"dispatch togglespecialworkspace scratch",
+			"dispatch togglespecialworkspace",
+		]);
+	}
+
+	#[test]
+	fn leaves_a_shown_special_workspace_alone() {
+		let _env = lock_env();
+		let hyprland = FakeHyprland::serve(
+			"shown",
+			json!({
+				"monitors": [{ "name": "DP-1", "specialWorkspace": { "id": -98, "name": "special:scratch" } }],
+			}),
+		);
+
+		reveal_special_workspace("special:scratch").unwrap();
+		assert!(hyprland.dispatches().is_empty());
+	}
+}
@@ -0,0 +1,334 @@
+//! Focus tracking for sway and i3 through their shared IPC protocol.
+
+use std::{
+	io::{self, Read, Write},
+	os::unix::net::UnixStream,
+	path::PathBuf,
+	time::{Duration, Instant},
+};
+
+use serde_json::Value;
+use tracing::warn;
+
+use super::linux::PendingInsertTarget;
+
+const MAGIC: &[u8] = b"i3-ipc";
+
+const RUN_COMMAND: u32 = 0;
+const SUBSCRIBE: u32 = 2;
+const GET_TREE: u32 = 4;
+
+/// Message type of window events, which have the high bit set.
+const WINDOW_EVENT: u32 = 0x8000_0003;
+
+/// The IPC socket of the running sway or i3 instance, if any.
+pub(crate) fn socket_path() -> Option<PathBuf> {
+	std::env::var_os("SWAYSOCK").or_else(|| std::env::var_os("I3SOCK")).map(PathBuf::from)
+}
+
+fn connect() -> io::Result<UnixStream> {
+	let path = socket_path()
+		.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "neither SWAYSOCK nor I3SOCK is set"))?;
+	UnixStream::connect(path)
+}
+
+fn send(stream: &mut UnixStream, kind: u32, payload: &str) -> io::Result<()> {
+	let mut message = Vec::with_capacity(MAGIC.len() + 8 + payload.len());
+	message.extend_from_slice(MAGIC);
+	message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+	message.extend_from_slice(&kind.to_ne_bytes());
+	message.extend_from_slice(payload.as_bytes());
+	stream.write_all(&message)
+}
+
+/// Reads one reply or event and returns its type along with it.
+fn receive(stream: &mut UnixStream) -> io::Result<(u32, Value)> {
+	let mut header = [0; MAGIC.len() + 8];
+	stream.read_exact(&mut header)?;
+	if &header[..MAGIC.len()] != MAGIC {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, "reply lacks the i3-ipc magic"));
+	}
+	let length = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]);
+	let kind = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+
+	let mut body = vec![0; length as usize];
+	stream.read_exact(&mut body)?;
+	let body =
+		serde_json::from_slice(&body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+	Ok((kind, body))
+}
+
+/// Sends one message and reads back its reply.
+fn request(kind: u32, payload: &str) -> io::Result<Value> {
+	let mut stream = connect()?;
+	send(&mut stream, kind, payload)?;
+	receive(&mut stream).map(|(_, reply)| reply)
+}
+
+/// Depth-first search for the container that has focus.
+fn focused_node(node: &Value) -> Option<&Value> {
+	if node["focused"].as_bool() == Some(true) {
+		return Some(node);
+	}
+
+	["nodes", "floating_nodes"]
+		.iter()
+		.filter_map(|key| node[key].as_array())
+		.flatten()
+		.find_map(focused_node)
+}
+
+pub(crate) fn capture_focused_container() -> PendingInsertTarget {
+	let tree = match request(GET_TREE, "") {
+		Ok(tree) => tree,
+		Err(e) => {
+			warn!("failed to read the sway/i3 layout tree: {e}");
+			return PendingInsertTarget::default();
+		}
+	};
+
+	let Some(node) = focused_node(&tree) else {
+		warn!("no focused container in the sway/i3 layout tree");
+		return PendingInsertTarget::default();
+	};
+
+	// Native Wayland windows carry an `app_id`; XWayland and i3 windows a class.
+	let class = node["app_id"].as_str().or_else(|| node["window_properties"]["class"].as_str());
+
+	PendingInsertTarget {
+		i3_con_id: node["id"].as_i64(),
+		class: class.map(String::from),
+		title: node["name"].as_str().map(String::from),
+		..Default::default()
+	}
+}
+
+/// Id of the container that has focus right now.
+pub(crate) fn focused_container_id() -> io::Result<Option<i64>> {
+	let tree = request(GET_TREE, "")?;
+	Ok(focused_node(&tree).and_then(|node| node["id"].as_i64()))
+}
+
+/// Waits for the container to have focus, following the window events sway
+/// and i3 send as focus moves; `false` on timeout.
+pub(crate) fn wait_for_focus(con_id: i64, timeout: Duration) -> io::Result<bool> {
+	let deadline = Instant::now() + timeout;
+	let mut events = connect()?;
+	send(&mut events, SUBSCRIBE, r#"["window"]"#)?;
+	let (_, reply) = receive(&mut events)?;
+	if reply["success"].as_bool() != Some(true) {
+		return Err(io::Error::other("subscribing to window events was refused"));
+	}
+
+	// Focus may have moved before the subscription took.
+	if focused_container_id()? == Some(con_id) {
+		return Ok(true);
+	}
+
+	loop {
+		let left = deadline.saturating_duration_since(Instant::now());
+		if left.is_zero() {
+			return Ok(false);
+		}
+		events.set_read_timeout(Some(left))?;
+		match receive(&mut events) {
+			Ok((WINDOW_EVENT, event))
+				if event["change"] == "focus" && event["container"]["id"].as_i64() == Some(con_id) =>
+			{
+				return Ok(true);
+			}
+			Ok(_) => {}
+			Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+				return Ok(false);
+			}
+			Err(e) => return Err(e),
+		}
+	}
+}
+
+/// Gives focus back to a container captured before the picker opened.
+pub(crate) fn focus_container(con_id: i64) -> io::Result<()> {
+	let reply = request(RUN_COMMAND, &format!("[con_id={con_id}] focus"))?;
+
+	let outcome = reply.as_array().and_then(|outcomes| outcomes.first());
+	match outcome {
+		Some(outcome) if outcome["success"].as_bool() == Some(true) => Ok(()),
+		Some(outcome) => Err(io::Error::other(
+			outcome["error"].as_str().unwrap_or("focus command was rejected").to_string(),
+		)),
+		None => Err(io::Error::new(io::ErrorKind::InvalidData, "empty reply to focus command")),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		os::unix::net::UnixListener,
+		sync::{Arc, Mutex},
+		thread,
+	};
+
+	use serde_json::json;
+
+	use super::*;
+	use crate::test_support::{lock_env, remove_env, set_env};
+
+	/// A stand-in sway/i3 IPC socket that answers `get_tree` with `tree` and
+	/// `run_command` with `command_reply`, sends `window_events` to subscribers,
+	/// and records every request.
+	struct FakeIpc {
+		path:     PathBuf,
+		requests: Arc<Mutex<Vec<(u32, String)>>>,
+	}
+
+	impl FakeIpc {
+		fn serve(name: &str, tree: Value, command_reply: Value) -> Self {
+			Self::serve_with_events(name, tree, command_reply, Vec::new())
+		}
+
+		fn serve_with_events(
+			name: &str,
+			tree: Value,
+			command_reply: Value,
+			window_events: Vec<Value>,
+		) -> Self {
+			let path = std::env::temp_dir().join(format!("bob-i3-{name}-{}.sock", std::process::id()));
+			let _ = std::fs::remove_file(&path);
+			let listener = UnixListener::bind(&path).expect("fake socket binds");
+			let requests = Arc::new(Mutex::new(Vec::new()));
+
+			let log = requests.clone();
+			thread::spawn(move || {
+				// Subscribers stay connected.
+				let mut subscribers = Vec::new();
+				for mut stream in listener.incoming().flatten() {
+					let mut header = [0; MAGIC.len() + 8];
+					if stream.read_exact(&mut header).is_err() {
+						continue;
+					}
+					let length = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]);
+					let kind = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+					let mut payload = vec![0; length as usize];
+					if stream.read_exact(&mut payload).is_err() {
+						continue;
+					}
+					log.lock().unwrap().push((kind, String::from_utf8_lossy(&payload).into_owned()));
+
+					let body = match kind {
+						GET_TREE => tree.to_string(),
+						RUN_COMMAND => command_reply.to_string(),
+						SUBSCRIBE => json!({ "success": true }).to_string(),
+						_ => "[]".to_string(),
+					};
+					let _ = send(&mut stream, kind, &body);
+					if kind == SUBSCRIBE {
+						for event in &window_events {
+							let _ = send(&mut stream, WINDOW_EVENT, &event.to_string());
+						}
+						subscribers.push(stream);
+					}
+				}
+			});
+
+			set_env("SWAYSOCK", &path);
+			Self { path, requests }
+		}
+
+		fn requests(&self) -> Vec<(u32, String)> { self.requests.lock().unwrap().clone() }
+	}
+
+	impl Drop for FakeIpc {
+		fn drop(&mut self) {
+			remove_env("SWAYSOCK");
+			let _ = std::fs::remove_file(&self.path);
+		}
+	}
+
+	/// A workspace holding a terminal and a focused floating browser.
+	fn tree() -> Value {
+		json!({
+			"id": 1,
+			"focused": false,
+			"nodes": [{
+				"id": 10,
+				"focused": false,
+				"nodes": [{ "id": 11, "focused": false, "app_id": "foot", "name": "~" }],
+				"floating_nodes": [{
+					"id": 42,
+					"focused": true,
+					"app_id": null,
+					"window_properties": { "class": "firefox" },
+					"name": "Mozilla Firefox",
+				}],
+			}],
+		})
+	}
+
+	#[test]
+	fn captures_the_focused_container() {
+		let _env = lock_env();
+		let ipc = FakeIpc::serve("capture", tree(), json!([{ "success": true }]));
+
+		let target = capture_focused_container();
+		assert_eq!(target.i3_con_id, Some(42));
+		assert_eq!(target.class.as_deref(), Some("firefox"));
+		assert_eq!(target.title.as_deref(), Some("Mozilla Firefox"));
+		assert_eq!(focused_container_id().unwrap(), Some(42));
+		assert_eq!(ipc.requests(), [(GET_TREE, String::new()), (GET_TREE, String::new())]);
+	}
+
+	#[test]
+	fn refocuses_by_con_id() {
+		let _env = lock_env();
+		let ipc = FakeIpc::serve("focus", tree(), json!([{ "success": true }]));
+
+		focus_container(42).expect("focus command succeeds");
+		assert_eq!(ipc.requests(), [(RUN_COMMAND, "[con_id=42] focus".to_string())]);
+	}
+
+	#[test]
+	fn waits_for_the_focus_event() {
+		let _env = lock_env();
+		let focus = |id| json!({ "change": "focus", "container": { "id": id } });
+		let ipc = FakeIpc::serve_with_events("events", tree(), json!([{ "success": true }]), vec![
+			json!({ "change": "title", "container": { "id": 11 } }),
+			focus(10),
+			focus(11),
+		]);
+
+		assert!(wait_for_focus(11, Duration::from_secs(1)).unwrap());
+		assert_eq!(ipc.requests(), [
+			(SUBSCRIBE, r#"["window"]"#.to_string()),
+			(GET_TREE, String::new())
+		]);
+	}
+
+	#[test]
+	fn focus_already_there_needs_no_event() {
+		let _env = lock_env();
+		let _ipc = FakeIpc::serve("focused", tree(), json!([{ "success": true }]));
+
+		assert!(wait_for_focus(42, Duration::from_secs(1)).unwrap());
+	}
+
+	#[test]
+	fn focus_elsewhere_times_out() {
+		let _env = lock_env();
+		let _ipc = FakeIpc::serve("elsewhere", tree(), json!([{ "success": true }]));
+
+		assert!(!wait_for_focus(11, Duration::from_millis(50)).unwrap());
+	}
+
+	#[test]
+	fn reports_a_rejected_focus_command() {
+		let _env = lock_env();
+		let _ipc = FakeIpc::serve(
+			"rejected",
+			tree(),
+			json!([{ "success": false, "error": "No matching node" }]),
+		);
+
+		let error = focus_container(7).expect_err("focus command is rejected");
+		assert_eq!(error.to_string(), "No matching node");
+	}
+}
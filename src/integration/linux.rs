@@ -1,22 +1,54 @@
-//! Linux session detection, Hyprland support, and terminal paste class
-//! handling.
+//! Linux session detection and focus capture for the compositors we can target.
 
-use tracing::warn;
+use std::{io, os::fd::AsRawFd, sync::OnceLock, time::Duration};
+
+use tracing::{debug, warn};
+use wayland_client::{
+	Connection, Dispatch, EventQueue, QueueHandle,
+	globals::{GlobalListContents, registry_queue_init},
+	protocol::wl_registry,
+};
+
+/// MIME type, or X11 selection target, that clipboard managers such as Klipper
+/// and cliphist take as a sign that an offer should stay out of their history.
+pub(crate) const CLIPBOARD_MANAGER_HINT: &str = "x-kde-passwordManagerHint";
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub(crate) enum LinuxSession {
 	WaylandHyprland,
+	WaylandSway,
+	WaylandNiri,
+	WaylandRiver,
 	WaylandOther,
 	X11,
 	Unknown,
 }
 
+impl LinuxSession {
+	pub(crate) fn is_wayland(self) -> bool {
+		matches!(
+			self,
+			Self::WaylandHyprland
+				| Self::WaylandSway
+				| Self::WaylandNiri
+				| Self::WaylandRiver
+				| Self::WaylandOther
+		)
+	}
+}
+
 pub(crate) fn detect_linux_session() -> LinuxSession {
 	let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some()
 		|| std::env::var("XDG_SESSION_TYPE").ok().as_deref() == Some("wayland");
 	if wayland {
 		if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
 			LinuxSession::WaylandHyprland
+		} else if std::env::var_os("SWAYSOCK").is_some() {
+			LinuxSession::WaylandSway
+		} else if std::env::var_os("NIRI_SOCKET").is_some() {
+			LinuxSession::WaylandNiri
+		} else if is_river() {
+			LinuxSession::WaylandRiver
 		} else {
 			LinuxSession::WaylandOther
 		}
@@ -27,54 +59,168 @@ pub(crate) fn detect_linux_session() -> LinuxSession {
 	}
 }
 
+/// river sets no variable of its own, so fall back to the desktop name and
+/// then to the globals only river advertises.
+fn is_river() -> bool {
+	let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+	desktop.split(':').any(|name| name.eq_ignore_ascii_case("river"))
+		|| wayland_advertises("zriver_control_v1")
+}
+
+struct GlobalsProbe;
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for GlobalsProbe {
+	fn event(
+		_: &mut Self,
+		_: &wl_registry::WlRegistry,
+		_: wl_registry::Event,
+		_: &GlobalListContents,
+		_: &Connection,
+		_: &QueueHandle<Self>,
+	) {
+	}
+}
+
+/// Interface names the Wayland compositor advertises, probed once per process.
+/// Empty outside Wayland or when the compositor cannot be reached.
+pub(crate) fn wayland_globals() -> &'static [String] {
+	static GLOBALS: OnceLock<Vec<String>> = OnceLock::new();
+
+	GLOBALS.get_or_init(|| {
+		let connection = match Connection::connect_to_env() {
+			Ok(connection) => connection,
+			Err(e) => {
+				debug!("not probing Wayland globals: {e}");
+				return Vec::new();
+			}
+		};
+
+		match registry_queue_init::<GlobalsProbe>(&connection) {
+			Ok((globals, _)) => {
+				let interfaces: Vec<String> =
+					globals.contents().clone_list().into_iter().map(|global| global.interface).collect();
+				debug!(?interfaces, "probed Wayland globals");
+				interfaces
+			}
+			Err(e) => {
+				warn!("failed to list Wayland globals: {e}");
+				Vec::new()
+			}
+		}
+	})
+}
+
+pub(crate) fn wayland_advertises(interface: &str) -> bool {
+	wayland_globals().iter().any(|global| global == interface)
+}
+
+/// Blocks until the compositor sends events or `timeout` passes, then
+/// dispatches whatever arrived.
+pub(crate) fn dispatch_within<D: 'static>(
+	queue: &mut EventQueue<D>,
+	state: &mut D,
+	timeout: Duration,
+) -> io::Result<()> {
+	queue.flush().map_err(io::Error::other)?;
+	if let Some(guard) = queue.prepare_read() {
+		let mut fd = libc::pollfd {
+			fd:      guard.connection_fd().as_raw_fd(),
+			events:  libc::POLLIN,
+			revents: 0,
+		};
+		let millis = timeout.as_millis().clamp(1, i32::MAX as u128) as i32;
+		match unsafe { libc::poll(&mut fd, 1, millis) } {
+			0 => {}
+			ready if ready > 0 => {
+				guard.read().map_err(io::Error::other)?;
+			}
+			_ => {
+				let e = io::Error::last_os_error();
+				if e.kind() != io::ErrorKind::Interrupted {
+					return Err(e);
+				}
+			}
+		}
+	}
+	queue.dispatch_pending(state).map_err(io::Error::other)?;
+	Ok(())
+}
+
+/// Text-entry and clipboard protocols the compositor offers, which decide the
+/// insertion backends worth trying on Wayland.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct WaylandCapabilities {
+	pub virtual_keyboard: bool,
+	pub input_method:     bool,
+	/// Whether a client without a focused surface can own the clipboard, which
+	/// pasting needs. Mutter offers no such protocol.
+	pub data_control:     bool,
+}
+
+pub(crate) fn wayland_capabilities() -> WaylandCapabilities {
+	WaylandCapabilities {
+		virtual_keyboard: wayland_advertises("zwp_virtual_keyboard_manager_v1"),
+		input_method:     wayland_advertises("zwp_input_method_manager_v2"),
+		data_control:     wayland_advertises("ext_data_control_manager_v1")
+			|| wayland_advertises("zwlr_data_control_manager_v1"),
+	}
+}
+
 /// Stores the window that was focused before the picker opened.
-/// Captured via `hyprctl activewindow -j` on Hyprland/Wayland.
+/// Captured through the compositor's IPC socket on Hyprland, sway, i3 and niri,
+/// from `_NET_ACTIVE_WINDOW` on X11, and from river-status on river, which
+/// reports only a title.
 #[derive(Clone, Debug, Default)]
 pub(crate) struct PendingInsertTarget {
 	pub hyprland_address: Option<String>,
+	pub i3_con_id:        Option<i64>,
+	pub niri_window_id:   Option<u64>,
+	pub x11_window:       Option<u32>,
 	pub class:            Option<String>,
+	pub title:            Option<String>,
 }
 
 impl gpui::Global for PendingInsertTarget {}
 
-/// Terminal window classes that need Ctrl+Shift+V instead of Ctrl+V.
-pub(crate) const SHIFT_PASTE_CLASSES: &[&str] = &[
-	"kitty",
-	"alacritty",
-	"foot",
-	"wezterm",
-	"terminator",
-	"tilix",
-	"gnome-terminal",
-	"konsole",
-	"xterm",
-	"urxvt",
-	"st",
-	"rio",
-	"ghostty",
-];
-
-pub(crate) fn capture_hyprland_active_window() -> PendingInsertTarget {
-	use std::process::Command;
-
-	let output = match Command::new("hyprctl").args(["activewindow", "-j"]).output() {
-		Ok(o) => o,
-		Err(e) => {
-			warn!("failed to run hyprctl activewindow: {e}");
-			return PendingInsertTarget::default();
+impl PendingInsertTarget {
+	/// Whether `other` is the same window, judged by the first id both captures
+	/// carry; `None` when they have none in common, as on river, where captures
+	/// only hold a title.
+	pub(crate) fn same_window(&self, other: &Self) -> Option<bool> {
+		fn compare<T: PartialEq>(a: &Option<T>, b: &Option<T>) -> Option<bool> {
+			Some(a.as_ref()? == b.as_ref()?)
 		}
-	};
 
-	let json: serde_json::Value = match serde_json::from_slice(&output.stdout) {
-		Ok(v) => v,
-		Err(e) => {
-			warn!("failed to parse hyprctl activewindow JSON: {e}");
-			return PendingInsertTarget::default();
-		}
-	};
+		compare(&self.hyprland_address, &other.hyprland_address)
+			.or_else(|| compare(&self.i3_con_id, &other.i3_con_id))
+			.or_else(|| compare(&self.niri_window_id, &other.niri_window_id))
+			.or_else(|| compare(&self.x11_window, &other.x11_window))
+	}
+}
 
-	PendingInsertTarget {
-		hyprland_address: json["address"].as_str().map(String::from),
-		class:            json["class"].as_str().map(String::from),
+/// Records the focused window by whichever means the session offers; empty
+/// when there is none.
+///
+/// river only reports a title, which app rules can match, but nothing to
+/// refocus by, so insertion goes to whatever regains focus once the picker
+/// closes.
+pub(crate) fn capture_focused_window() -> PendingInsertTarget {
+	match detect_linux_session() {
+		LinuxSession::WaylandHyprland => super::hyprland_ipc::capture_active_window(),
+		LinuxSession::WaylandNiri => super::niri::capture_focused_window(),
+		LinuxSession::WaylandRiver => super::river::capture_focused_view(),
+		LinuxSession::X11 => {
+			let window = super::x11::capture_active_window();
+			if super::i3ipc::socket_path().is_some() {
+				PendingInsertTarget {
+					x11_window: window.x11_window,
+					..super::i3ipc::capture_focused_container()
+				}
+			} else {
+				window
+			}
+		}
+		_ if super::i3ipc::socket_path().is_some() => super::i3ipc::capture_focused_container(),
+		_ => PendingInsertTarget::default(),
 	}
 }
@@ -1,4 +1,4 @@
-//! macOS accessory policy to hide the app from the Dock.
+//! macOS accessory policy to hide the app from the Dock, and focus checks.
 
 use tracing::debug;
 
@@ -13,3 +13,14 @@ pub(crate) fn set_accessory_policy() {
 		let _: () = msg_send![&*app, setActivationPolicy: NSApplicationActivationPolicy::Accessory];
 	}
 }
+
+/// Whether some other application is frontmost, i.e. the picker has handed
+/// focus back.
+pub(crate) fn frontmost_is_elsewhere() -> bool {
+	unsafe {
+		use objc2_app_kit::NSWorkspace;
+		NSWorkspace::sharedWorkspace()
+			.frontmostApplication()
+			.is_some_and(|app| app.processIdentifier() != std::process::id() as i32)
+	}
+}
@@ -1,7 +1,28 @@
 //! Platform integration helpers.
 
+#[cfg(target_os = "linux")]
+pub(crate) mod foreign_toplevel;
+
+#[cfg(target_os = "linux")]
+pub(crate) mod hyprland_ipc;
+
+#[cfg(target_os = "linux")]
+pub(crate) mod i3ipc;
+
 #[cfg(target_os = "linux")]
 pub(crate) mod linux;
 
+#[cfg(target_os = "linux")]
+pub(crate) mod niri;
+
+#[cfg(target_os = "linux")]
+pub(crate) mod notifications;
+
+#[cfg(target_os = "linux")]
+pub(crate) mod river;
+
+#[cfg(target_os = "linux")]
+pub(crate) mod x11;
+
 #[cfg(target_os = "macos")]
 pub(crate) mod macos;
@@ -0,0 +1,271 @@
+//! Focus tracking for niri through its JSON IPC socket.
+
+use std::{
+	io::{self, BufRead, BufReader, Write},
+	os::unix::net::UnixStream,
+	time::{Duration, Instant},
+};
+
+use serde_json::{Value, json};
+use tracing::warn;
+
+use super::linux::PendingInsertTarget;
+
+/// Sends one request and returns the `Ok` payload of its reply. niri reads a
+/// single line of JSON and answers with a single line.
+fn request(request: &Value) -> io::Result<Value> { read_reply(&mut send(request)?) }
+
+/// Sends one request line on a fresh connection, which is returned for reading
+/// what niri answers.
+fn send(request: &Value) -> io::Result<BufReader<UnixStream>> {
+	let path = std::env::var_os("NIRI_SOCKET")
+		.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "NIRI_SOCKET is not set"))?;
+	let mut stream = UnixStream::connect(path)?;
+
+	stream.write_all(format!("{request}\n").as_bytes())?;
+	stream.flush()?;
+	Ok(BufReader::new(stream))
+}
+
+fn read_json(reader: &mut BufReader<UnixStream>) -> io::Result<Value> {
+	let mut line = String::new();
+	if reader.read_line(&mut line)? == 0 {
+		return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "niri closed the connection"));
+	}
+	serde_json::from_str(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn read_reply(reader: &mut BufReader<UnixStream>) -> io::Result<Value> {
+	let mut reply = read_json(reader)?;
+	match reply.get_mut("Ok") {
+		Some(ok) => Ok(ok.take()),
+		None => Err(io::Error::other(
+			reply["Err"].as_str().unwrap_or("malformed reply from niri").to_string(),
+		)),
+	}
+}
+
+pub(crate) fn capture_focused_window() -> PendingInsertTarget {
+	let reply = match request(&json!("FocusedWindow")) {
+		Ok(reply) => reply,
+		Err(e) => {
+			warn!("failed to ask niri for the focused window: {e}");
+			return PendingInsertTarget::default();
+		}
+	};
+
+	// `null` when no window has focus.
+	let window = &reply["FocusedWindow"];
+	PendingInsertTarget {
+		niri_window_id: window["id"].as_u64(),
+		class: window["app_id"].as_str().map(String::from),
+		title: window["title"].as_str().map(String::from),
+		..Default::default()
+	}
+}
+
+/// Waits for the window to have focus, following niri's event stream, which
+/// opens with the current state; `false` on timeout.
+pub(crate) fn wait_for_focus(id: u64, timeout: Duration) -> io::Result<bool> {
+	let deadline = Instant::now() + timeout;
+	let mut events = send(&json!("EventStream"))?;
+	read_reply(&mut events)?;
+
+	loop {
+		let left = deadline.saturating_duration_since(Instant::now());
+		if left.is_zero() {
+			return Ok(false);
+		}
+		events.get_ref().set_read_timeout(Some(left))?;
+		match read_json(&mut events) {
+			Ok(event) if focused_in(&event) == Some(id) => return Ok(true),
+			Ok(_) => {}
+			Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+				return Ok(false);
+			}
+			Err(e) => return Err(e),
+		}
+	}
+}
+
+/// The window an event says has focus, when it says.
+fn focused_in(event: &Value) -> Option<u64> {
+	if let Some(changed) = event.get("WindowFocusChanged") {
+		return changed["id"].as_u64();
+	}
+
+	let listed = event["WindowsChanged"]["windows"].as_array().into_iter().flatten();
+	let changed = event.get("WindowOpenedOrChanged").map(|changed| &changed["window"]);
+	listed
+		.chain(changed)
+		.filter(|window| window["is_focused"].as_bool() == Some(true))
+		.find_map(|window| window["id"].as_u64())
+}
+
+/// Gives focus back to a window captured before the picker opened.
+pub(crate) fn focus_window(id: u64) -> io::Result<()> {
+	request(&json!({ "Action": { "FocusWindow": { "id": id } } })).map(drop)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		os::unix::net::UnixListener,
+		path::PathBuf,
+		sync::{Arc, Mutex},
+		thread,
+	};
+
+	use super::*;
+	use crate::test_support::{lock_env, remove_env, set_env};
+
+	/// A stand-in niri socket. Requests get `replies[name]` as their `Ok`
+	/// payload, `name` being the request itself when it is a string and its
+	/// only key otherwise; the event stream sends `events`. Every request is
+	/// recorded.
+	struct FakeNiri {
+		path:     PathBuf,
+		requests: Arc<Mutex<Vec<Value>>>,
+	}
+
+	impl FakeNiri {
+		fn serve(name: &str, replies: Value, events: Vec<Value>) -> Self {
+			let path = std::env::temp_dir().join(format!("bob-niri-{name}-{}.sock", std::process::id()));
+			let _ = std::fs::remove_file(&path);
+			let listener = UnixListener::bind(&path).expect("fake socket binds");
+			let requests = Arc::new(Mutex::new(Vec::new()));
+
+			let log = requests.clone();
+			thread::spawn(move || {
+				// Event stream readers stay connected.
+				let mut streams = Vec::new();
+				for mut stream in listener.incoming().flatten() {
+					let mut line = String::new();
+					if BufReader::new(&stream).read_line(&mut line).is_err() {
+						continue;
+					}
+					let Ok(request) = serde_json::from_str::<Value>(&line) else { continue };
+					log.lock().unwrap().push(request.clone());
+
+					let name = match &request {
+						Value::String(name) => name.clone(),
+						request => {
+							request.as_object().and_then(|o| o.keys().next().cloned()).unwrap_or_default()
+						}
+					};
+					let reply = match name.as_str() {
+						"EventStream" => json!({ "Ok": "Handled" }),
+						name => match replies.get(name) {
+							Some(reply) => json!({ "Ok": reply }),
+							None => json!({ "Err": format!("unexpected request {name}") }),
+						},
+					};
+					let _ = writeln!(stream, "{reply}");
+					if name == "EventStream" {
+						for event in &events {
+							let _ = writeln!(stream, "{event}");
+						}
+						streams.push(stream);
+					}
+				}
+			});
+
+			set_env("NIRI_SOCKET", &path);
+			Self { path, requests }
+		}
+
+		fn requests(&self) -> Vec<Value> { self.requests.lock().unwrap().clone() }
+	}
+
+	impl Drop for FakeNiri {
+		fn drop(&mut self) {
+			remove_env("NIRI_SOCKET");
+			let _ = std::fs::remove_file(&self.path);
+		}
+	}
+
+	fn window(id: u64, focused: bool) -> Value {
+		json!({ "id": id, "app_id": "foot", "title": "~", "is_focused": focused })
+	}
+
+	#[test]
+	fn captures_the_focused_window() {
+		let _env = lock_env();
+		let niri = FakeNiri::serve(
+			"capture",
+			json!({ "FocusedWindow": { "FocusedWindow": window(7, true) } }),
+			Vec::new(),
+		);
+
+		let target = capture_focused_window();
+		assert_eq!(target.niri_window_id, Some(7));
+		assert_eq!(target.class.as_deref(), Some("foot"));
+		assert_eq!(target.title.as_deref(), Some("~"));
+		assert_eq!(niri.requests(), [json!("FocusedWindow")]);
+	}
+
+	#[test]
+	fn nothing_focused_captures_nothing() {
+		let _env = lock_env();
+		let _niri = FakeNiri::serve(
+			"unfocused",
+			json!({ "FocusedWindow": { "FocusedWindow": null } }),
+			Vec::new(),
+		);
+
+		let target = capture_focused_window();
+		assert_eq!(target.niri_window_id, None);
+		assert_eq!(target.title, None);
+	}
+
+	#[test]
+	fn refocuses_by_window_id() {
+		let _env = lock_env();
+		let niri = FakeNiri::serve("focus", json!({ "Action": "Handled" }), Vec::new());
+
+		focus_window(7).expect("focus action succeeds");
+		assert_eq!(niri.requests(), [json!({ "Action": { "FocusWindow": { "id": 7 } } })]);
+	}
+
+	#[test]
+	fn reports_a_rejected_request() {
+		let _env = lock_env();
+		let _niri = FakeNiri::serve("rejected", json!({}), Vec::new());
+
+		let error = focus_window(7).expect_err("the action is refused");
+		assert_eq!(error.to_string(), "unexpected request Action");
+	}
+
+	#[test]
+	fn waits_for_the_focus_event() {
+		let _env = lock_env();
+		let niri = FakeNiri::serve("events", json!({}), vec![
+			json!({ "WindowsChanged": { "windows": [window(3, true), window(7, false)] } }),
+			json!({ "WindowFocusChanged": { "id": null } }),
+			json!({ "WindowFocusChanged": { "id": 7 } }),
+		]);
+
+		assert!(wait_for_focus(7, Duration::from_secs(1)).unwrap());
+		assert_eq!(niri.requests(), [json!("EventStream")]);
+	}
+
+	#[test]
+	fn focus_already_there_shows_in_the_initial_state() {
+		let _env = lock_env();
+		let _niri = FakeNiri::serve("focused", json!({}), vec![json!({
+			"WindowsChanged": { "windows": [window(3, false), window(7, true)] }
+		})]);
+
+		assert!(wait_for_focus(7, Duration::from_secs(1)).unwrap());
+	}
+
+	#[test]
+	fn focus_elsewhere_times_out() {
+		let _env = lock_env();
+		let _niri = FakeNiri::serve("elsewhere", json!({}), vec![json!({
+			"WindowsChanged": { "windows": [window(3, true), window(7, false)] }
+		})]);
+
+		assert!(!wait_for_focus(7, Duration::from_millis(50)).unwrap());
+	}
+}
@@ -0,0 +1,37 @@
+//! Desktop notifications over the `org.freedesktop.Notifications` D-Bus
+//! interface.
+
+use std::collections::HashMap;
+
+use tracing::warn;
+use zbus::{blocking::Connection, zvariant::Value};
+
+const DESTINATION: &str = "org.freedesktop.Notifications";
+const PATH: &str = "/org/freedesktop/Notifications";
+const INTERFACE: &str = "org.freedesktop.Notifications";
+
+const APP_NAME: &str = "bob";
+
+/// How long a notice stays up, in milliseconds.
+const EXPIRE_TIMEOUT: i32 = 5000;
+
+/// Shows a notice through the session bus, logging rather than failing when no
+/// notification daemon is around.
+pub(crate) fn notify(summary: &str, body: &str) {
+	// Notices about one insertion are of no use once it is over.
+	let hints = HashMap::from([("transient", Value::from(true))]);
+	let actions: &[&str] = &[];
+
+	let shown = Connection::session().and_then(|connection| {
+		connection.call_method(
+			Some(DESTINATION),
+			PATH,
+			Some(INTERFACE),
+			"Notify",
+			&(APP_NAME, 0u32, "", summary, body, actions, hints, EXPIRE_TIMEOUT),
+		)
+	});
+	if let Err(e) = shown {
+		warn!("failed to show a notification: {e}");
+	}
+}
@@ -0,0 +1,1014 @@
+//! Focus tracking on X11 through the EWMH `_NET_ACTIVE_WINDOW` property, and
+//! the CLIPBOARD and PRIMARY selection handling that X11 insertion needs.
+
+use std::{
+	io::{self, Read, Write},
+	os::fd::AsRawFd,
+	sync::mpsc,
+	thread,
+	time::{Duration, Instant},
+};
+
+use tracing::{debug, warn};
+use x11rb::{
+	COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE,
+	connection::Connection,
+	protocol::{
+		Event,
+		xkb::{self, ConnectionExt as _},
+		xproto::{
+			AtomEnum, ChangeWindowAttributesAux, ClientMessageEvent, ConnectionExt, CreateWindowAux,
+			EventMask, GetPropertyReply, ModMask, PropMode, Property, SELECTION_NOTIFY_EVENT,
+			SelectionNotifyEvent, SelectionRequestEvent, Window, WindowClass,
+		},
+	},
+	rust_connection::RustConnection,
+	wrapper::ConnectionExt as _,
+};
+
+use super::linux::{CLIPBOARD_MANAGER_HINT, PendingInsertTarget};
+
+/// How long the window manager gets to hand focus back to the saved window.
+const ACTIVATION_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// `_NET_ACTIVE_WINDOW` source indication for pagers and similar tools, which
+/// window managers honour without focus-stealing prevention.
+const SOURCE_PAGER: u32 = 2;
+
+/// Runs the process as a holder for the selection named by the next argument
+/// instead of the picker; see [`hold_selection`].
+pub(crate) const SERVE_SELECTION_FLAG: &str = "--serve-x11-selection";
+
+const CLIPBOARD: &str = "CLIPBOARD";
+const PRIMARY: &str = "PRIMARY";
+
+/// How long the current selection owner gets to hand over its contents.
+const SELECTION_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Largest selection target handed over in one go, and the chunk size of the
+/// incremental transfers that larger ones go through.
+const INCR_CHUNK: usize = 64 * 1024;
+
+/// Targets that describe or act on a selection rather than hold its contents.
+const META_TARGETS: &[&str] = &[
+	"TARGETS",
+	"MULTIPLE",
+	"TIMESTAMP",
+	"SAVE_TARGETS",
+	"DELETE",
+	"INSERT_SELECTION",
+	"INSERT_PROPERTY",
+];
+
+fn x11_error(e: impl std::fmt::Display) -> io::Error { io::Error::other(e.to_string()) }
+
+struct SelectionAtoms {
+	selection:   u32,
+	targets:     u32,
+	utf8_string: u32,
+	text_plain:  u32,
+	incr:        u32,
+	timestamp:   u32,
+	hint:        u32,
+	/// Property on our own window that converted selections are written to.
+	transfer:    u32,
+}
+
+impl SelectionAtoms {
+	fn text_targets(&self) -> [u32; 2] { [self.utf8_string, self.text_plain] }
+
+	/// `text` as every text target, plus the clipboard manager hint when
+	/// `transient`.
+	fn text_offers(&self, text: &[u8], transient: bool) -> Vec<SelectionData> {
+		let mut offers: Vec<SelectionData> = self
+			.text_targets()
+			.into_iter()
+			.map(|target| SelectionData { target, kind: target, format: 8, data: text.to_vec() })
+			.collect();
+		if transient {
+			offers.push(SelectionData {
+				target: self.hint,
+				kind:   self.hint,
+				format: 8,
+				data:   b"secret".to_vec(),
+			});
+		}
+		offers
+	}
+}
+
+/// One target a selection is offered as, in the type and format its owner
+/// handed it over in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SelectionData {
+	target: u32,
+	kind:   u32,
+	format: u8,
+	data:   Vec<u8>,
+}
+
+impl SelectionData {
+	/// Writes the data for a selection holder; atoms are the same in every
+	/// client of the display, so they are passed as they are.
+	fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+		out.write_all(&self.target.to_ne_bytes())?;
+		out.write_all(&self.kind.to_ne_bytes())?;
+		out.write_all(&[self.format])?;
+		out.write_all(&(self.data.len() as u32).to_ne_bytes())?;
+		out.write_all(&self.data)
+	}
+
+	/// Reads what [`write_to`](Self::write_to) wrote; `None` at the end.
+	fn read_from(input: &mut impl Read) -> io::Result<Option<Self>> {
+		let mut word = [0; 4];
+		match input.read_exact(&mut word) {
+			Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+			read => read?,
+		}
+		let target = u32::from_ne_bytes(word);
+		input.read_exact(&mut word)?;
+		let kind = u32::from_ne_bytes(word);
+		let mut format = [0];
+		input.read_exact(&mut format)?;
+		input.read_exact(&mut word)?;
+		let mut data = vec![0; u32::from_ne_bytes(word) as usize];
+		input.read_exact(&mut data)?;
+		Ok(Some(Self { target, kind, format: format[0], data }))
+	}
+}
+
+/// The CLIPBOARD as it was before a paste, as every target its owner offered.
+/// Holds nothing when no client owned it.
+pub(crate) struct SavedClipboard(Vec<SelectionData>);
+
+/// An offer too large for one request on its way to a requestor, a chunk each
+/// time the requestor deletes the property it reads them from.
+struct Transfer<'a> {
+	requestor: Window,
+	property:  u32,
+	offer:     &'a SelectionData,
+	sent:      usize,
+}
+
+struct XConnection {
+	connection: RustConnection,
+	root:       Window,
+}
+
+impl XConnection {
+	fn open() -> io::Result<Self> {
+		let (connection, screen) = x11rb::connect(None).map_err(x11_error)?;
+		let root = connection.setup().roots[screen].root;
+		Ok(Self { connection, root })
+	}
+
+	fn atom(&self, name: &str) -> io::Result<u32> {
+		Ok(
+			self
+				.connection
+				.intern_atom(false, name.as_bytes())
+				.map_err(x11_error)?
+				.reply()
+				.map_err(x11_error)?
+				.atom,
+		)
+	}
+
+	fn property(&self, window: Window, property: u32, kind: u32) -> io::Result<Vec<u8>> {
+		Ok(
+			self
+				.connection
+				.get_property(false, window, property, kind, 0, u32::MAX / 4)
+				.map_err(x11_error)?
+				.reply()
+				.map_err(x11_error)?
+				.value,
+		)
+	}
+
+	fn active_window(&self) -> io::Result<Option<Window>> {
+		let net_active_window = self.atom("_NET_ACTIVE_WINDOW")?;
+		let reply = self
+			.connection
+			.get_property(false, self.root, net_active_window, AtomEnum::WINDOW, 0, 1)
+			.map_err(x11_error)?
+			.reply()
+			.map_err(x11_error)?;
+		Ok(reply.value32().and_then(|mut windows| windows.next()).filter(|window| *window != 0))
+	}
+
+	/// The process behind a window, from `_NET_WM_PID`, which most clients set.
+	fn pid(&self, window: Window) -> io::Result<Option<u32>> {
+		let net_wm_pid = self.atom("_NET_WM_PID")?;
+		let reply = self
+			.connection
+			.get_property(false, window, net_wm_pid, AtomEnum::CARDINAL, 0, 1)
+			.map_err(x11_error)?
+			.reply()
+			.map_err(x11_error)?;
+		Ok(reply.value32().and_then(|mut pids| pids.next()))
+	}
+
+	/// The class half of `WM_CLASS`, which holds `instance\0class\0`.
+	fn class(&self, window: Window) -> io::Result<Option<String>> {
+		let value = self.property(window, AtomEnum::WM_CLASS.into(), AtomEnum::STRING.into())?;
+		Ok(
+			value
+				.split(|byte| *byte == 0)
+				.nth(1)
+				.filter(|class| !class.is_empty())
+				.map(|class| String::from_utf8_lossy(class).into_owned()),
+		)
+	}
+
+	fn title(&self, window: Window) -> io::Result<Option<String>> {
+		let mut value = self.property(window, self.atom("_NET_WM_NAME")?, self.atom("UTF8_STRING")?)?;
+		if value.is_empty() {
+			value = self.property(window, AtomEnum::WM_NAME.into(), AtomEnum::STRING.into())?;
+		}
+		Ok((!value.is_empty()).then(|| String::from_utf8_lossy(&value).into_owned()))
+	}
+
+	fn request_activation(&self, window: Window) -> io::Result<()> {
+		let event = ClientMessageEvent::new(32, window, self.atom("_NET_ACTIVE_WINDOW")?, [
+			SOURCE_PAGER,
+			CURRENT_TIME,
+			0,
+			0,
+			0,
+		]);
+		self
+			.connection
+			.send_event(
+				false,
+				self.root,
+				EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+				event,
+			)
+			.map_err(x11_error)?;
+		self.connection.flush().map_err(x11_error)
+	}
+
+	fn selection_atoms(&self, selection: &str) -> io::Result<SelectionAtoms> {
+		Ok(SelectionAtoms {
+			selection:   self.atom(selection)?,
+			targets:     self.atom("TARGETS")?,
+			utf8_string: self.atom("UTF8_STRING")?,
+			text_plain:  self.atom("text/plain;charset=utf-8")?,
+			incr:        self.atom("INCR")?,
+			timestamp:   self.atom("TIMESTAMP")?,
+			hint:        self.atom(CLIPBOARD_MANAGER_HINT)?,
+			transfer:    self.atom("BOB_SELECTION")?,
+		})
+	}
+
+	/// An unmapped window to own selections and receive converted ones. It
+	/// hears about its property changes, which incremental transfers and server
+	/// timestamps rely on.
+	fn helper_window(&self) -> io::Result<Window> {
+		let window = self.connection.generate_id().map_err(x11_error)?;
+		self
+			.connection
+			.create_window(
+				COPY_DEPTH_FROM_PARENT,
+				window,
+				self.root,
+				0,
+				0,
+				1,
+				1,
+				0,
+				WindowClass::INPUT_ONLY,
+				COPY_FROM_PARENT,
+				&CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+			)
+			.map_err(x11_error)?;
+		Ok(window)
+	}
+
+	/// The current server time, read off the notification for an empty append
+	/// to a property of `window`, a helper window. Selection ownership has to
+	/// be timestamped with it rather than `CurrentTime`.
+	fn server_time(&self, window: Window, atoms: &SelectionAtoms) -> io::Result<u32> {
+		self
+			.connection
+			.change_property8(PropMode::APPEND, window, atoms.transfer, AtomEnum::STRING, &[])
+			.map_err(x11_error)?;
+		self.connection.flush().map_err(x11_error)?;
+
+		let deadline = Instant::now() + SELECTION_TIMEOUT;
+		while let Some(event) = self.next_event(deadline)? {
+			if let Event::PropertyNotify(notify) = event
+				&& notify.window == window
+				&& notify.atom == atoms.transfer
+			{
+				return Ok(notify.time);
+			}
+		}
+
+		Err(io::Error::new(io::ErrorKind::TimedOut, "the X server did not report the time"))
+	}
+
+	/// The next event, sleeping on the connection until one arrives; `None` once
+	/// the deadline passes.
+	fn next_event(&self, deadline: Instant) -> io::Result<Option<Event>> {
+		loop {
+			if let Some(event) = self.connection.poll_for_event().map_err(x11_error)? {
+				return Ok(Some(event));
+			}
+			let left = deadline.saturating_duration_since(Instant::now());
+			if left.is_zero() {
+				return Ok(None);
+			}
+
+			let mut fd = libc::pollfd {
+				fd:      self.connection.stream().as_raw_fd(),
+				events:  libc::POLLIN,
+				revents: 0,
+			};
+			// Round up so a wait shorter than a millisecond does not spin.
+			let millis = left.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32;
+			if unsafe { libc::poll(&mut fd, 1, millis) } < 0 {
+				let e = io::Error::last_os_error();
+				if e.kind() != io::ErrorKind::Interrupted {
+					return Err(e);
+				}
+			}
+		}
+	}
+
+	/// Takes the selection and returns the server time it was taken at.
+	fn take_selection(&self, atoms: &SelectionAtoms) -> io::Result<u32> {
+		let window = self.helper_window()?;
+		let time = self.server_time(window, atoms)?;
+		self.connection.set_selection_owner(window, atoms.selection, time).map_err(x11_error)?;
+		if self.selection_owner(atoms)? != window {
+			return Err(io::Error::other("could not take ownership of the selection"));
+		}
+		Ok(time)
+	}
+
+	fn selection_owner(&self, atoms: &SelectionAtoms) -> io::Result<Window> {
+		Ok(
+			self
+				.connection
+				.get_selection_owner(atoms.selection)
+				.map_err(x11_error)?
+				.reply()
+				.map_err(x11_error)?
+				.owner,
+		)
+	}
+
+	/// Asks the selection owner for one target, reading it in chunks when the
+	/// owner hands it over incrementally. `None` when it refuses.
+	fn convert(
+		&self,
+		window: Window,
+		atoms: &SelectionAtoms,
+		target: u32,
+	) -> io::Result<Option<GetPropertyReply>> {
+		self
+			.connection
+			.convert_selection(window, atoms.selection, target, atoms.transfer, CURRENT_TIME)
+			.map_err(x11_error)?;
+		self.connection.flush().map_err(x11_error)?;
+
+		let deadline = Instant::now() + SELECTION_TIMEOUT;
+		while let Some(event) = self.next_event(deadline)? {
+			// Late answers to an earlier conversion are not this one.
+			let Event::SelectionNotify(notify) = event else {
+				continue;
+			};
+			if notify.target != target {
+				continue;
+			}
+			if notify.property == NONE {
+				return Ok(None);
+			}
+
+			let reply = self.take_property(window, atoms.transfer)?;
+			if reply.type_ == atoms.incr {
+				debug!(target, "reading a selection target incrementally");
+				return self.receive_incrementally(window, atoms).map(Some);
+			}
+			return Ok(Some(reply));
+		}
+
+		Err(io::Error::new(io::ErrorKind::TimedOut, "the selection owner did not answer"))
+	}
+
+	/// Reads and deletes a property of our own window.
+	fn take_property(&self, window: Window, property: u32) -> io::Result<GetPropertyReply> {
+		self
+			.connection
+			.get_property(true, window, property, AtomEnum::ANY, 0, u32::MAX / 4)
+			.map_err(x11_error)?
+			.reply()
+			.map_err(x11_error)
+	}
+
+	/// Reads a target the owner hands over in chunks. Deleting the property,
+	/// which reading the `INCR` announcement already did, asks for each next
+	/// chunk, and an empty one ends the transfer.
+	fn receive_incrementally(
+		&self,
+		window: Window,
+		atoms: &SelectionAtoms,
+	) -> io::Result<GetPropertyReply> {
+		let mut received: Option<GetPropertyReply> = None;
+		loop {
+			let deadline = Instant::now() + SELECTION_TIMEOUT;
+			let chunk = loop {
+				match self.next_event(deadline)? {
+					Some(Event::PropertyNotify(notify))
+						if notify.window == window
+							&& notify.atom == atoms.transfer
+							&& notify.state == Property::NEW_VALUE =>
+					{
+						break self.take_property(window, atoms.transfer)?;
+					}
+					Some(_) => {}
+					None => {
+						return Err(io::Error::new(
+							io::ErrorKind::TimedOut,
+							"the selection owner stopped handing over data",
+						));
+					}
+				}
+			};
+
+			match &mut received {
+				_ if chunk.value.is_empty() => return Ok(received.unwrap_or(chunk)),
+				Some(received) => received.value.extend(chunk.value),
+				None => received = Some(chunk),
+			}
+		}
+	}
+
+	/// Answers requests for the selection, taken at `owned_at`, until another
+	/// client takes it, calling `on_served` whenever text was handed over.
+	fn serve_selection(
+		&self,
+		atoms: &SelectionAtoms,
+		offers: &[SelectionData],
+		owned_at: u32,
+		mut on_served: impl FnMut(),
+	) -> io::Result<()> {
+		let mut transfers = Vec::new();
+		loop {
+			match self.connection.wait_for_event().map_err(x11_error)? {
+				Event::SelectionRequest(request) => {
+					let handed_over = self.answer(atoms, &request, offers, owned_at, &mut transfers)?;
+					if handed_over {
+						on_served();
+					}
+				}
+				Event::PropertyNotify(notify) if notify.state == Property::DELETE => {
+					let waiting = transfers.iter().position(|transfer: &Transfer| {
+						transfer.requestor == notify.window && transfer.property == notify.atom
+					});
+					if let Some(index) = waiting
+						&& !self.send_chunk(&mut transfers[index])?
+					{
+						transfers.swap_remove(index);
+					}
+				}
+				Event::SelectionClear(clear) if clear.selection == atoms.selection => return Ok(()),
+				_ => {}
+			}
+		}
+	}
+
+	/// Answers one request, starting an incremental transfer for offers too
+	/// large for one request; `true` when it asked for text.
+	fn answer<'a>(
+		&self,
+		atoms: &SelectionAtoms,
+		request: &SelectionRequestEvent,
+		offers: &'a [SelectionData],
+		owned_at: u32,
+		transfers: &mut Vec<Transfer<'a>>,
+	) -> io::Result<bool> {
+		// Obsolete clients leave the property unset and expect the target instead.
+		let property = if request.property == NONE { request.target } else { request.property };
+		let offer = offers.iter().find(|offer| offer.target == request.target);
+		// Requests timestamped before the selection was taken were meant for the
+		// previous owner.
+		let stale = request.time != CURRENT_TIME && request.time < owned_at;
+
+		let stored = if stale {
+			NONE
+		} else if request.target == atoms.targets {
+			let mut targets = vec![atoms.targets, atoms.timestamp];
+			targets.extend(offers.iter().map(|offer| offer.target));
+			self
+				.connection
+				.change_property32(PropMode::REPLACE, request.requestor, property, AtomEnum::ATOM, &targets)
+				.map_err(x11_error)?;
+			property
+		} else if request.target == atoms.timestamp {
+			self
+				.connection
+				.change_property32(PropMode::REPLACE, request.requestor, property, AtomEnum::INTEGER, &[
+					owned_at,
+				])
+				.map_err(x11_error)?;
+			property
+		} else if let Some(offer) = offer {
+			if offer.data.len() <= INCR_CHUNK {
+				self.store(request.requestor, property, offer, &offer.data)?;
+			} else {
+				// Announce the size; the chunks follow as the requestor deletes
+				// the property, which it only tells us about if we ask.
+				self
+					.connection
+					.change_window_attributes(
+						request.requestor,
+						&ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+					)
+					.map_err(x11_error)?;
+				self
+					.connection
+					.change_property32(PropMode::REPLACE, request.requestor, property, atoms.incr, &[offer
+						.data
+						.len()
+						as u32])
+					.map_err(x11_error)?;
+				transfers.push(Transfer { requestor: request.requestor, property, offer, sent: 0 });
+			}
+			property
+		} else {
+			NONE
+		};
+
+		let notify = SelectionNotifyEvent {
+			response_type: SELECTION_NOTIFY_EVENT,
+			sequence:      0,
+			time:          request.time,
+			requestor:     request.requestor,
+			selection:     request.selection,
+			target:        request.target,
+			property:      stored,
+		};
+		self
+			.connection
+			.send_event(false, request.requestor, EventMask::NO_EVENT, notify)
+			.map_err(x11_error)?;
+		self.connection.flush().map_err(x11_error)?;
+		Ok(stored != NONE && atoms.text_targets().contains(&request.target))
+	}
+
+	/// Writes the next chunk of an incremental transfer, or the empty one that
+	/// ends it; `false` once it has ended.
+	fn send_chunk(&self, transfer: &mut Transfer) -> io::Result<bool> {
+		let data = &transfer.offer.data;
+		let chunk = &data[transfer.sent..data.len().min(transfer.sent + INCR_CHUNK)];
+		self.store(transfer.requestor, transfer.property, transfer.offer, chunk)?;
+		self.connection.flush().map_err(x11_error)?;
+		transfer.sent += chunk.len();
+		Ok(!chunk.is_empty())
+	}
+
+	/// Writes `data`, all or part of `offer`, to a property of the requestor.
+	fn store(
+		&self,
+		requestor: Window,
+		property: u32,
+		offer: &SelectionData,
+		data: &[u8],
+	) -> io::Result<()> {
+		self
+			.connection
+			.change_property(
+				PropMode::REPLACE,
+				requestor,
+				property,
+				offer.kind,
+				offer.format,
+				data.len() as u32 / (u32::from(offer.format) / 8),
+				data,
+			)
+			.map_err(x11_error)?;
+		Ok(())
+	}
+}
+
+pub(crate) fn capture_active_window() -> PendingInsertTarget {
+	let captured = XConnection::open().and_then(|display| {
+		let Some(window) = display.active_window()? else {
+			return Ok(PendingInsertTarget::default());
+		};
+
+		Ok(PendingInsertTarget {
+			x11_window: Some(window),
+			class: display.class(window)?,
+			title: display.title(window)?,
+			..Default::default()
+		})
+	});
+
+	captured.unwrap_or_else(|e| {
+		warn!("failed to capture the active X11 window: {e}");
+		PendingInsertTarget::default()
+	})
+}
+
+/// Asks the window manager to activate the saved window and waits for
+/// `_NET_ACTIVE_WINDOW` on the root window to say it has.
+pub(crate) fn activate_window(window: Window) -> io::Result<()> {
+	let display = XConnection::open()?;
+	let net_active_window = display.atom("_NET_ACTIVE_WINDOW")?;
+	display
+		.connection
+		.change_window_attributes(
+			display.root,
+			&ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+		)
+		.map_err(x11_error)?;
+	if display.active_window()? == Some(window) {
+		return Ok(());
+	}
+	display.request_activation(window)?;
+
+	let deadline = Instant::now() + ACTIVATION_TIMEOUT;
+	while let Some(event) = display.next_event(deadline)? {
+		if let Event::PropertyNotify(notify) = event
+			&& notify.window == display.root
+			&& notify.atom == net_active_window
+			&& display.active_window()? == Some(window)
+		{
+			return Ok(());
+		}
+	}
+
+	Err(io::Error::new(io::ErrorKind::TimedOut, format!("window {window:#x} did not become active")))
+}
+
+/// Waits until a window of some other process is active, i.e. until the picker
+/// has handed focus back, checking each time `_NET_ACTIVE_WINDOW` changes;
+/// `false` on timeout.
+pub(crate) fn wait_for_focus_elsewhere(timeout: Duration) -> io::Result<bool> {
+	let display = XConnection::open()?;
+	let net_active_window = display.atom("_NET_ACTIVE_WINDOW")?;
+	let own_pid = std::process::id();
+	let deadline = Instant::now() + timeout;
+	display
+		.connection
+		.change_window_attributes(
+			display.root,
+			&ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+		)
+		.map_err(x11_error)?;
+
+	let focus_elsewhere = || -> io::Result<bool> {
+		Ok(match display.active_window()? {
+			Some(window) => display.pid(window)? != Some(own_pid),
+			None => false,
+		})
+	};
+	if focus_elsewhere()? {
+		return Ok(true);
+	}
+	while let Some(event) = display.next_event(deadline)? {
+		if let Event::PropertyNotify(notify) = event
+			&& notify.window == display.root
+			&& notify.atom == net_active_window
+			&& focus_elsewhere()?
+		{
+			return Ok(true);
+		}
+	}
+	Ok(false)
+}
+
+/// Waits until no Shift, Control, Alt or Super key is held, so synthetic keys
+/// do not combine with what is left of a global shortcut, following the XKB
+/// state notifications; `false` on timeout.
+pub(crate) fn wait_for_modifiers_released(timeout: Duration) -> io::Result<bool> {
+	let display = XConnection::open()?;
+	let held = ModMask::SHIFT | ModMask::CONTROL | ModMask::M1 | ModMask::M4;
+	let deadline = Instant::now() + timeout;
+
+	let connection = &display.connection;
+	let xkb = connection.xkb_use_extension(1, 0).map_err(x11_error)?.reply().map_err(x11_error)?;
+	if !xkb.supported {
+		return Err(io::Error::new(io::ErrorKind::Unsupported, "the X server lacks XKB"));
+	}
+	let keyboard = xkb::ID::USE_CORE_KBD.into();
+	connection
+		.xkb_select_events(
+			keyboard,
+			xkb::EventType::from(0u16),
+			xkb::EventType::STATE_NOTIFY,
+			xkb::MapPart::from(0u16),
+			xkb::MapPart::from(0u16),
+			&xkb::SelectEventsAux::new(),
+		)
+		.map_err(x11_error)?
+		.check()
+		.map_err(x11_error)?;
+
+	// Keys may have been let go before the selection took.
+	let state = connection.xkb_get_state(keyboard).map_err(x11_error)?.reply().map_err(x11_error)?;
+	if u16::from(state.base_mods & held) == 0 {
+		return Ok(true);
+	}
+	while let Some(event) = display.next_event(deadline)? {
+		if let Event::XkbStateNotify(notify) = event
+			&& u16::from(notify.base_mods & held) == 0
+		{
+			return Ok(true);
+		}
+	}
+	Ok(false)
+}
+
+/// Reads the PRIMARY selection, i.e. the text currently selected, as UTF-8.
+/// `None` when it is empty or holds no text.
+pub(crate) fn read_primary() -> io::Result<Option<Vec<u8>>> {
+	let display = XConnection::open()?;
+	let atoms = display.selection_atoms(PRIMARY)?;
+	let window = display.helper_window()?;
+
+	let reply = display.convert(window, &atoms, atoms.utf8_string)?;
+	Ok(reply.map(|reply| reply.value).filter(|value| !value.is_empty()))
+}
+
+/// Reads the CLIPBOARD as every target its owner lists, so images, rich text
+/// and file lists survive a paste along with plain text. Fails rather than
+/// report an owned clipboard as empty when nothing could be read from it.
+pub(crate) fn save_clipboard() -> io::Result<SavedClipboard> {
+	let display = XConnection::open()?;
+	let atoms = display.selection_atoms(CLIPBOARD)?;
+	if display.selection_owner(&atoms)? == NONE {
+		return Ok(SavedClipboard(Vec::new()));
+	}
+	let window = display.helper_window()?;
+
+	let meta_targets =
+		META_TARGETS.iter().map(|name| display.atom(name)).collect::<io::Result<Vec<_>>>()?;
+	let mut targets: Vec<u32> = display
+		.convert(window, &atoms, atoms.targets)?
+		.and_then(|reply| reply.value32().map(Iterator::collect))
+		.unwrap_or_default();
+	targets.retain(|target| !meta_targets.contains(target));
+	// Owners that cannot list their targets are still asked for text.
+	if targets.is_empty() {
+		targets.extend(atoms.text_targets());
+	}
+
+	let mut saved = Vec::new();
+	for target in targets {
+		match display.convert(window, &atoms, target) {
+			Ok(Some(reply)) if reply.format != 0 => saved.push(SelectionData {
+				target,
+				kind: reply.type_,
+				format: reply.format,
+				data: reply.value,
+			}),
+			Ok(_) => {}
+			Err(e) => debug!(target, "skipping a clipboard target: {e}"),
+		}
+	}
+
+	if saved.is_empty() {
+		return Err(io::Error::other("the clipboard owner handed nothing over"));
+	}
+	Ok(SavedClipboard(saved))
+}
+
+/// Takes the CLIPBOARD selection and serves `contents` from a background
+/// thread until another client takes it, marked so clipboard managers skip it.
+/// The receiver hears about every request for the text.
+pub(crate) fn own_clipboard(contents: Vec<u8>) -> io::Result<mpsc::Receiver<()>> {
+	let display = XConnection::open()?;
+	let atoms = display.selection_atoms(CLIPBOARD)?;
+	let owned_at = display.take_selection(&atoms)?;
+
+	let offers = atoms.text_offers(&contents, true);
+	let (served_tx, served_rx) = mpsc::channel();
+	thread::spawn(move || {
+		let served = display.serve_selection(&atoms, &offers, owned_at, || {
+			let _ = served_tx.send(());
+		});
+		if let Err(e) = served {
+			warn!("stopped serving the X11 clipboard: {e}");
+		}
+	});
+
+	Ok(served_rx)
+}
+
+/// Puts the previous clipboard back in every target it was saved in. A
+/// clipboard that was empty is released again.
+pub(crate) fn restore_clipboard(saved: SavedClipboard) -> io::Result<()> {
+	if saved.0.is_empty() {
+		let display = XConnection::open()?;
+		let atoms = display.selection_atoms(CLIPBOARD)?;
+		let time = display.server_time(display.helper_window()?, &atoms)?;
+		display.connection.set_selection_owner(NONE, atoms.selection, time).map_err(x11_error)?;
+		return display.connection.flush().map_err(x11_error);
+	}
+
+	hold_selection(CLIPBOARD, &saved.0)
+}
+
+/// Makes `contents` the PRIMARY selection, so a middle-click pastes it.
+pub(crate) fn set_primary(contents: &[u8]) -> io::Result<()> {
+	let display = XConnection::open()?;
+	let atoms = display.selection_atoms(PRIMARY)?;
+	hold_selection(PRIMARY, &atoms.text_offers(contents, false))
+}
+
+/// X11 selections only live as long as their owner, which has to outlast a
+/// picker that quits after inserting, so a detached copy of the executable
+/// holds them.
+#[cfg(not(test))]
+fn hold_selection(selection: &str, offers: &[SelectionData]) -> io::Result<()> {
+	use std::process::{Command, Stdio};
+
+	let mut holder = Command::new(std::env::current_exe()?)
+		.args([SERVE_SELECTION_FLAG, selection])
+		.stdin(Stdio::piped())
+		.stdout(Stdio::null())
+		.stderr(Stdio::null())
+		.spawn()?;
+	let written = match holder.stdin.take() {
+		Some(mut stdin) => offers.iter().try_for_each(|offer| offer.write_to(&mut stdin)),
+		None => Err(io::Error::other("selection holder has no stdin")),
+	};
+	thread::spawn(move || holder.wait());
+	written
+}
+
+/// The test executable cannot stand in for a selection holder, but outlives
+/// the selection anyway, so a thread holds it instead.
+#[cfg(test)]
+fn hold_selection(selection: &str, offers: &[SelectionData]) -> io::Result<()> {
+	let display = XConnection::open()?;
+	let atoms = display.selection_atoms(selection)?;
+	let owned_at = display.take_selection(&atoms)?;
+	let offers = offers.to_vec();
+	thread::spawn(move || display.serve_selection(&atoms, &offers, owned_at, || {}));
+	Ok(())
+}
+
+/// Entry point for [`SERVE_SELECTION_FLAG`]: holds the targets read from stdin
+/// as the named selection until another client takes it over.
+pub(crate) fn serve_selection_from_stdin(selection: &str) {
+	let mut stdin = io::stdin().lock();
+	let mut offers = Vec::new();
+	loop {
+		match SelectionData::read_from(&mut stdin) {
+			Ok(Some(offer)) => offers.push(offer),
+			Ok(None) => break,
+			Err(e) => {
+				warn!("failed to read selection contents to hold: {e}");
+				return;
+			}
+		}
+	}
+
+	let served = XConnection::open().and_then(|display| {
+		let atoms = display.selection_atoms(selection)?;
+		let owned_at = display.take_selection(&atoms)?;
+		display.serve_selection(&atoms, &offers, owned_at, || {})
+	});
+	if let Err(e) = served {
+		warn!(selection, "failed to hold the X11 selection: {e}");
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_support::{PrivateDisplay, ScopedEnv, lock_env};
+
+	/// Starts a private X server and points `DISPLAY` at it; `None` without Xvfb.
+	fn private_display() -> Option<(PrivateDisplay, ScopedEnv)> {
+		let display = PrivateDisplay::start()?;
+		let mut env = ScopedEnv::default();
+		env.set("DISPLAY", &display.name);
+		Some((display, env))
+	}
+
+	/// Pastes: asks the CLIPBOARD owner for one target, as an application would.
+	fn paste(target: &str) -> Option<Vec<u8>> {
+		let display = XConnection::open().unwrap();
+		let atoms = display.selection_atoms(CLIPBOARD).unwrap();
+		let window = display.helper_window().unwrap();
+		let target = display.atom(target).unwrap();
+		display.convert(window, &atoms, target).unwrap().map(|reply| reply.value)
+	}
+
+	#[test]
+	fn the_clipboard_survives_a_paste() {
+		let _lock = lock_env();
+		let Some((_display, _env)) = private_display() else {
+			return;
+		};
+
+		// Another application's clipboard: text, and an image large enough to
+		// go through incremental transfers.
+		let owner = XConnection::open().unwrap();
+		let atoms = owner.selection_atoms(CLIPBOARD).unwrap();
+		let png = owner.atom("image/png").unwrap();
+		let image: Vec<u8> = (0..3 * INCR_CHUNK + 5).map(|i| i as u8).collect();
+		let mut original = atoms.text_offers(b"before", false);
+		original.push(SelectionData { target: png, kind: png, format: 8, data: image.clone() });
+		let owned_at = owner.take_selection(&atoms).unwrap();
+		let offers = original.clone();
+		thread::spawn(move || owner.serve_selection(&atoms, &offers, owned_at, || {}));
+
+		let saved = save_clipboard().unwrap();
+		let mut saved_offers = saved.0.clone();
+		saved_offers.sort_by_key(|offer| offer.target);
+		original.sort_by_key(|offer| offer.target);
+		assert_eq!(saved_offers, original);
+
+		let served = own_clipboard("🦀".as_bytes().to_vec()).unwrap();
+		assert_eq!(paste("UTF8_STRING").as_deref(), Some("🦀".as_bytes()));
+		served.recv_timeout(SELECTION_TIMEOUT).unwrap();
+
+		restore_clipboard(saved).unwrap();
+		assert_eq!(paste("UTF8_STRING").as_deref(), Some(&b"before"[..]));
+		assert_eq!(paste("image/png"), Some(image));
+	}
+
+	#[test]
+	fn activation_asks_once_and_waits_for_the_window_manager() {
+		let _lock = lock_env();
+		let Some((_display, _env)) = private_display() else {
+			return;
+		};
+
+		// A window manager that takes its time to activate what it is asked to.
+		let wm = XConnection::open().unwrap();
+		let net_active_window = wm.atom("_NET_ACTIVE_WINDOW").unwrap();
+		wm.connection
+			.change_window_attributes(
+				wm.root,
+				&ChangeWindowAttributesAux::new().event_mask(EventMask::SUBSTRUCTURE_REDIRECT),
+			)
+			.unwrap()
+			.check()
+			.unwrap();
+		let window = wm.helper_window().unwrap();
+		wm.connection.flush().unwrap();
+		let requests = thread::spawn(move || {
+			let mut requests = 0;
+			let deadline = Instant::now() + ACTIVATION_TIMEOUT;
+			while let Some(event) = wm.next_event(deadline).unwrap() {
+				if let Event::ClientMessage(message) = event
+					&& message.type_ == net_active_window
+				{
+					requests += 1;
+					thread::sleep(Duration::from_millis(50));
+					wm.connection
+						.change_property32(PropMode::REPLACE, wm.root, net_active_window, AtomEnum::WINDOW, &[
+							message.window,
+						])
+						.unwrap();
+					wm.connection.flush().unwrap();
+				}
+			}
+			requests
+		});
+
+		activate_window(window).unwrap();
+		assert_eq!(requests.join().unwrap(), 1);
+	}
+
+	#[test]
+	fn holder_input_round_trips_every_target() {
+		let offers = [
+			SelectionData { target: 301, kind: 301, format: 8, data: "🦀".as_bytes().to_vec() },
+			SelectionData { target: 302, kind: 4, format: 32, data: vec![1, 0, 0, 0, 2, 0, 0, 0] },
+			SelectionData { target: 303, kind: 303, format: 8, data: Vec::new() },
+		];
+
+		let mut input = Vec::new();
+		for offer in &offers {
+			offer.write_to(&mut input).unwrap();
+		}
+
+		let mut reader = input.as_slice();
+		let mut read = Vec::new();
+		while let Some(offer) = SelectionData::read_from(&mut reader).unwrap() {
+			read.push(offer);
+		}
+		assert_eq!(read, offers);
+	}
+
+	#[test]
+	fn truncated_holder_input_is_an_error() {
+		let mut input = Vec::new();
+		SelectionData { target: 301, kind: 301, format: 8, data: b"text".to_vec() }
+			.write_to(&mut input)
+			.unwrap();
+		input.pop();
+
+		assert!(SelectionData::read_from(&mut input.as_slice()).is_err());
+	}
+}
@@ -3,6 +3,8 @@
 use gpui::{Action, App, KeyBinding, actions};
 use serde::Deserialize;
 
+use crate::insert::format::OutputFormat;
+
 #[derive(Action, Clone, PartialEq, Eq, Deserialize)]
 #[action(namespace = input, no_json)]
 pub struct JumpToSection {
@@ -15,6 +17,30 @@ pub struct RotateTones {
 	pub direction: Direction,
 }
 
+#[derive(Action, Clone, PartialEq, Eq, Deserialize)]
+#[action(namespace = picker, no_json)]
+pub struct InsertAs {
+	pub format: OutputFormat,
+}
+
+#[derive(Action, Clone, PartialEq, Eq, Deserialize)]
+#[action(namespace = picker, no_json)]
+pub struct RepeatDigit {
+	pub digit: u32,
+}
+
+#[derive(Action, Clone, PartialEq, Eq, Deserialize)]
+#[action(namespace = tray, no_json)]
+pub struct MoveTrayCursor {
+	pub direction: Direction,
+}
+
+#[derive(Action, Clone, PartialEq, Eq, Deserialize)]
+#[action(namespace = tray, no_json)]
+pub struct ShiftStaged {
+	pub direction: Direction,
+}
+
 #[derive(Clone, PartialEq, Eq, Deserialize)]
 pub enum Direction {
 	Forward,
@@ -31,7 +57,12 @@ actions!(picker, [
 	SelectCurrent,
 	OpenSecondary,
 	FocusSearch,
+	Confirm,
+	SelectKeepOpen,
+	ToggleKeepOpen,
+	TogglePresentation,
 ]);
+actions!(tray, [StageCurrent, RemoveStaged]);
 
 pub(crate) fn bind_all(cx: &mut App) {
 	use Direction::{Backward, Forward};
@@ -40,13 +71,32 @@ pub(crate) fn bind_all(cx: &mut App) {
 		KeyBinding::new("super-q", Quit, None),
 		KeyBinding::new("super-w", Quit, None),
 		KeyBinding::new("escape", Cancel, None),
-		KeyBinding::new("enter", Cancel, None),
+		// Enter used to only cancel. It now inserts whatever is staged, and still
+		// cancels when the tray is empty.
+		KeyBinding::new("enter", Confirm, None),
+		KeyBinding::new("shift-enter", StageCurrent, Some("List")),
+		KeyBinding::new("alt-enter", SelectKeepOpen, Some("List")),
+		KeyBinding::new("alt-k", ToggleKeepOpen, Some("Picker")),
+		KeyBinding::new("alt-p", TogglePresentation, Some("Picker")),
+		// Only while something is staged, so the search input keeps these keys for
+		// moving and deleting by word otherwise.
+		KeyBinding::new("alt-left", MoveTrayCursor { direction: Backward }, Some("Tray")),
+		KeyBinding::new("alt-right", MoveTrayCursor { direction: Forward }, Some("Tray")),
+		KeyBinding::new("alt-shift-left", ShiftStaged { direction: Backward }, Some("Tray")),
+		KeyBinding::new("alt-shift-right", ShiftStaged { direction: Forward }, Some("Tray")),
+		KeyBinding::new("alt-backspace", RemoveStaged, Some("Tray")),
 		KeyBinding::new("up", MoveUp, Some("List")),
 		KeyBinding::new("down", MoveDown, Some("List")),
 		KeyBinding::new("left", MoveLeft, Some("List")),
 		KeyBinding::new("right", MoveRight, Some("List")),
 		KeyBinding::new(",", OpenSecondary, Some("List")),
 		KeyBinding::new("shift-space", SelectCurrent, Some("List")),
+		KeyBinding::new("alt-g", InsertAs { format: OutputFormat::Glyph }, Some("List")),
+		KeyBinding::new("alt-s", InsertAs { format: OutputFormat::Shortcode }, Some("List")),
+		KeyBinding::new("alt-c", InsertAs { format: OutputFormat::Codepoint }, Some("List")),
+		KeyBinding::new("alt-e", InsertAs { format: OutputFormat::Escape }, Some("List")),
+		KeyBinding::new("alt-h", InsertAs { format: OutputFormat::HtmlEntity }, Some("List")),
+		KeyBinding::new("alt-n", InsertAs { format: OutputFormat::Name }, Some("List")),
 		KeyBinding::new("space", SelectCurrent, Some("ListBody")),
 		KeyBinding::new("N", RotateTones { direction: Backward }, Some("ListBody")),
 		KeyBinding::new("n", RotateTones { direction: Forward }, Some("ListBody")),
@@ -59,6 +109,9 @@ pub(crate) fn bind_all(cx: &mut App) {
 
 	for n in 0..=9 {
 		bindings.push(KeyBinding::new(&format!("super-{n}"), JumpToSection { number: n }, None));
+		// Alt keeps digits free for the search input, which has focus.
+		let digit = RepeatDigit { digit: n as u32 };
+		bindings.push(KeyBinding::new(&format!("alt-{n}"), digit, Some("Picker")));
 	}
 
 	cx.bind_keys(bindings);
@@ -1,6 +1,8 @@
 //! Application lifecycle management for both service and non-service
 //! configurations.
 
+#[cfg(feature = "service")]
+use std::collections::HashMap;
 #[cfg(feature = "service")]
 use std::thread;
 #[cfg(feature = "service")]
@@ -24,8 +26,10 @@ use tracing::{debug, info};
 use crate::{
 	components::{
 		Picker,
-		types::{PopoverState, ToneIndex},
+		types::{PopoverState, PreviewPresentation, StagingTray, ToneIndex},
 	},
+	config::CONFIG,
+	insert::{KeepOpen, RepeatCount, selection},
 	keys::{self, Quit},
 	window_setup,
 };
@@ -44,28 +48,88 @@ pub(crate) fn run_app() {
 	#[cfg(feature = "service")]
 	let hotkey_manager = GlobalHotKeyManager::new().expect("failed to create hotkey manager");
 
+	#[cfg(all(feature = "service", target_os = "macos"))]
+	let modifiers = Modifiers::SUPER | Modifiers::SHIFT;
+	#[cfg(all(feature = "service", not(target_os = "macos")))]
+	let modifiers = Modifiers::CONTROL | Modifiers::SHIFT;
+
 	#[cfg(feature = "service")]
-	{
-		#[cfg(target_os = "macos")]
-		let modifiers = Modifiers::SUPER | Modifiers::SHIFT;
-		#[cfg(not(target_os = "macos"))]
-		let modifiers = Modifiers::CONTROL | Modifiers::SHIFT;
+	let picker_hotkey = HotKey::new(Some(modifiers), Code::KeyE);
+	#[cfg(feature = "service")]
+	hotkey_manager.register(picker_hotkey).expect("failed to register hotkey");
+
+	#[cfg(feature = "service")]
+	let reinsert_hotkey = HotKey::new(Some(modifiers | Modifiers::ALT), Code::KeyE);
+	#[cfg(feature = "service")]
+	if let Err(e) = hotkey_manager.register(reinsert_hotkey) {
+		report_hotkey_problem(&format!(
+			"re-insert hotkey could not be registered, probably because another application holds \
+			 it: {e}"
+		));
+	}
 
-		let hotkey = HotKey::new(Some(modifiers), Code::KeyE);
-		hotkey_manager.register(hotkey).expect("failed to register hotkey");
+	// Only Linux records which window an insertion went to, which undoing needs.
+	#[cfg(all(feature = "service", target_os = "linux"))]
+	let undo_hotkey = HotKey::new(Some(modifiers), Code::Backspace);
+	#[cfg(all(feature = "service", target_os = "linux"))]
+	if let Err(e) = hotkey_manager.register(undo_hotkey) {
+		report_hotkey_problem(&format!(
+			"undo hotkey could not be registered, probably because another application holds it: {e}"
+		));
 	}
 
+	#[cfg(feature = "service")]
+	let favorites = {
+		let mut taken = vec![(picker_hotkey, "opening the picker"), (reinsert_hotkey, "re-inserting")];
+		#[cfg(target_os = "linux")]
+		taken.push((undo_hotkey, "undoing the last insertion"));
+		register_favorites(&hotkey_manager, &taken)
+	};
+
 	let app = Application::new();
 
 	#[cfg(feature = "service")]
 	let (tx, rx) = std::sync::mpsc::channel();
 
+	// Hotkey actions that send keys can wait on modifiers, focus or a consent
+	// dialog, so they run on a worker and the loop keeps answering meanwhile.
+	#[cfg(feature = "service")]
+	let hotkey_work = spawn_hotkey_worker();
+
 	#[cfg(feature = "service")]
 	thread::spawn(move || {
 		let receiver = GlobalHotKeyEvent::receiver();
 		loop {
 			if let Ok(event) = receiver.recv() {
-				if event.state == global_hotkey::HotKeyState::Pressed {
+				// Act once the shortcut is let go, so its keys do not mix with the
+				// keys sent.
+				if let Some(text) = favorites.get(&event.id) {
+					if event.state == global_hotkey::HotKeyState::Released {
+						debug!(%text, "favorite hotkey released; inserting");
+						let text = text.clone();
+						let _ = hotkey_work.send(Box::new(move || insert_favorite(&text)));
+					}
+					continue;
+				}
+
+				if event.id == reinsert_hotkey.id() {
+					if event.state == global_hotkey::HotKeyState::Released {
+						debug!("re-insert hotkey released; repeating last insertion");
+						let _ = hotkey_work.send(Box::new(reinsert_last));
+					}
+					continue;
+				}
+
+				#[cfg(target_os = "linux")]
+				if event.id == undo_hotkey.id() {
+					if event.state == global_hotkey::HotKeyState::Released {
+						debug!("undo hotkey released; undoing last insertion");
+						let _ = hotkey_work.send(Box::new(undo_last_insertion));
+					}
+					continue;
+				}
+
+				if event.id == picker_hotkey.id() && event.state == global_hotkey::HotKeyState::Pressed {
 					debug!("hotkey pressed; toggling picker");
 					let _ = tx.send(());
 				}
@@ -76,6 +140,9 @@ pub(crate) fn run_app() {
 	app.run(|cx: &mut App| {
 		cx.set_global::<ToneIndex>(ToneIndex(0));
 		cx.set_global::<PopoverState>(PopoverState::default());
+		cx.set_global::<StagingTray>(StagingTray::default());
+		cx.set_global::<KeepOpen>(KeepOpen(CONFIG.keep_open));
+		cx.set_global::<PreviewPresentation>(PreviewPresentation(CONFIG.preview_presentation));
 
 		#[cfg(target_os = "macos")]
 		crate::integration::macos::set_accessory_policy();
@@ -119,19 +186,7 @@ pub(crate) fn run_app() {
 									}
 								}
 
-								#[cfg(target_os = "linux")]
-								{
-									use crate::integration::linux::{
-										LinuxSession, PendingInsertTarget, capture_hyprland_active_window,
-										detect_linux_session,
-									};
-									if detect_linux_session() == LinuxSession::WaylandHyprland {
-										let target = capture_hyprland_active_window();
-										cx.set_global::<PendingInsertTarget>(target);
-									}
-								}
-
-								initialize(cx);
+								open_picker(cx);
 							})
 							.expect("context should be available");
 					}
@@ -142,24 +197,124 @@ pub(crate) fn run_app() {
 		.detach();
 
 		#[cfg(not(feature = "service"))]
-		{
-			#[cfg(target_os = "linux")]
-			{
-				use crate::integration::linux::{
-					LinuxSession, PendingInsertTarget, capture_hyprland_active_window, detect_linux_session,
-				};
-				if detect_linux_session() == LinuxSession::WaylandHyprland {
-					let target = capture_hyprland_active_window();
-					cx.set_global::<PendingInsertTarget>(target);
-				}
+		open_picker(cx);
+	});
+}
+
+/// Registers the favorite hotkeys from the config, skipping and reporting any
+/// that do not parse, clash with `taken` or each other, or are held by another
+/// application. Returns the text to insert for each registered hotkey id.
+#[cfg(feature = "service")]
+fn register_favorites(
+	manager: &GlobalHotKeyManager,
+	taken: &[(HotKey, &str)],
+) -> HashMap<u32, String> {
+	let mut bound: HashMap<u32, String> =
+		taken.iter().map(|(hotkey, action)| (hotkey.id(), action.to_string())).collect();
+	let mut favorites = HashMap::new();
+
+	for (shortcut, text) in &CONFIG.favorites {
+		let hotkey: HotKey = match shortcut.parse() {
+			Ok(hotkey) => hotkey,
+			Err(e) => {
+				report_hotkey_problem(&format!("favorite hotkey `{shortcut}` is not a shortcut: {e}"));
+				continue;
 			}
+		};
+		if let Some(action) = bound.get(&hotkey.id()) {
+			report_hotkey_problem(&format!(
+				"favorite hotkey `{shortcut}` for {text} is already used for {action}"
+			));
+			continue;
+		}
+		if let Err(e) = manager.register(hotkey) {
+			report_hotkey_problem(&format!(
+				"favorite hotkey `{shortcut}` for {text} could not be registered, probably because \
+				 another application holds it: {e}"
+			));
+			continue;
+		}
+
+		debug!(%shortcut, %text, "favorite hotkey registered");
+		bound.insert(hotkey.id(), format!("inserting {text}"));
+		favorites.insert(hotkey.id(), text.clone());
+	}
+
+	favorites
+}
+
+/// Work a hotkey hands off to the worker.
+#[cfg(feature = "service")]
+type HotkeyJob = Box<dyn FnOnce() + Send>;
 
-			initialize(cx);
+/// Starts the thread that runs hotkey actions one at a time, in the order they
+/// were triggered, and returns where to send them.
+#[cfg(feature = "service")]
+fn spawn_hotkey_worker() -> std::sync::mpsc::Sender<HotkeyJob> {
+	let (jobs, queued) = std::sync::mpsc::channel::<HotkeyJob>();
+	thread::spawn(move || {
+		for job in queued {
+			job();
 		}
 	});
+	jobs
+}
+
+/// Inserts the most recent pick again; failures are logged here and notified
+/// by the insertion itself.
+#[cfg(feature = "service")]
+fn reinsert_last() {
+	if let Err(e) = crate::insert::reinsert(1) {
+		tracing::warn!("nothing re-inserted: {e}");
+	}
+}
+
+/// Inserts the text of a favorite hotkey; failures are logged here and
+/// notified by the insertion itself, as for picks.
+#[cfg(feature = "service")]
+fn insert_favorite(text: &str) {
+	if let Err(e) = crate::insert::insert_now(text) {
+		tracing::warn!(%text, "favorite not inserted: {e}");
+	}
+}
+
+/// Logs a hotkey that could not be set up, and tells the desktop too since the
+/// service runs without a terminal.
+#[cfg(feature = "service")]
+fn report_hotkey_problem(message: &str) {
+	tracing::warn!("{message}");
+	#[cfg(target_os = "linux")]
+	if CONFIG.notify != crate::insert::notify::Notify::Never {
+		crate::integration::notifications::notify("Hotkey not set up", message);
+	}
+}
+
+#[cfg(all(feature = "service", target_os = "linux"))]
+fn undo_last_insertion() {
+	use crate::insert::{notify::Notify, undo};
+
+	if let Err(e) = undo::undo_last() {
+		tracing::warn!("last insertion not undone: {e}");
+		if CONFIG.notify != Notify::Never {
+			crate::integration::notifications::notify("Nothing undone", &e.to_string());
+		}
+	}
+}
+
+/// Records the window the picker is opened over, then opens the picker.
+pub(crate) fn open_picker(cx: &mut App) {
+	#[cfg(target_os = "linux")]
+	{
+		use crate::integration::linux::{PendingInsertTarget, capture_focused_window};
+		cx.set_global::<PendingInsertTarget>(capture_focused_window());
+	}
+
+	cx.set_global(RepeatCount::default());
+	let query = if CONFIG.query_from_primary { selection::read_query() } else { None };
+	initialize(query, cx);
 }
 
-fn initialize(cx: &mut App) {
+fn initialize(query: Option<String>, cx: &mut App) {
 	let rem_size = 16.0;
 	let display_size =
 		cx.displays().first().map(|d| d.bounds().size).unwrap_or_else(|| size(px(1280.0), px(720.0)));
@@ -185,8 +340,11 @@ fn initialize(cx: &mut App) {
 			let picker = cx.new(|cx| Picker::new(window, cx));
 
 			window.activate_window();
-			let list_state = picker.read(cx).list_state.clone();
-			list_state.update(cx, |list, cx| list.focus(window, cx));
+			picker.update(cx, |picker, cx| picker.focus_search(window, cx));
+			if let Some(query) = query {
+				debug!(%query, "searching for the primary selection");
+				picker.update(cx, |picker, cx| picker.search_for(&query, window, cx));
+			}
 
 			cx.set_global::<AppState>(AppState {
 				picker: picker.clone(),
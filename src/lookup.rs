@@ -0,0 +1,20 @@
+//! Emoji lookup by glyph, shared by search, history and formatting.
+
+use std::{collections::HashMap, sync::LazyLock};
+
+use emoji::{Emoji, lookup_by_glyph::ALL_EMOJI};
+
+/// Every emoji by glyph, skin-toned variants included; built once.
+static BY_GLYPH: LazyLock<HashMap<&'static str, &'static Emoji>> = LazyLock::new(|| {
+	let mut index = HashMap::new();
+	for emoji in ALL_EMOJI
+		.iter()
+		.flat_map(|&emoji| std::iter::once(emoji).chain(emoji.skin_tones.into_iter().flatten()))
+	{
+		index.entry(emoji.glyph).or_insert(emoji);
+	}
+	index
+});
+
+/// The emoji with this glyph, skin-toned variants included.
+pub(crate) fn by_glyph(glyph: &str) -> Option<&'static Emoji> { BY_GLYPH.get(glyph).copied() }
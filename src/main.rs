@@ -1,19 +1,55 @@
 //! B.O.B (BOB offers... beauty): a fast, keyboard-driven emoji picker.
 
 mod components;
+mod config;
 mod emoji_sizing;
+mod history;
 mod insert;
 mod integration;
 mod keys;
 mod lifecycle;
 mod logging;
+mod lookup;
 #[cfg(feature = "service")]
 mod service;
+#[cfg(test)]
+mod test_support;
 mod window_setup;
 
 fn main() {
 	logging::init();
 
+	#[cfg(target_os = "linux")]
+	{
+		let mut args = std::env::args().skip_while(|arg| arg != integration::x11::SERVE_SELECTION_FLAG);
+		if args.next().is_some() {
+			let selection = args.next().unwrap_or_else(|| "CLIPBOARD".to_string());
+			integration::x11::serve_selection_from_stdin(&selection);
+			return;
+		}
+		if std::env::args().any(|arg| arg == insert::wayland::SERVE_CLIPBOARD_FLAG) {
+			insert::wayland::serve_clipboard_from_stdin();
+			return;
+		}
+	}
+
+	// `reinsert [N]` inserts the N-th most recent pick again without a window.
+	if std::env::args().nth(1).as_deref() == Some("reinsert") {
+		let n = match std::env::args().nth(2).map(|n| n.parse()) {
+			None => 1,
+			Some(Ok(n)) => n,
+			Some(Err(e)) => {
+				eprintln!("reinsert takes the position of a recent insertion, counting from 1: {e}");
+				std::process::exit(2);
+			}
+		};
+		if let Err(e) = insert::reinsert(n) {
+			eprintln!("nothing re-inserted: {e}");
+			std::process::exit(1);
+		}
+		return;
+	}
+
 	#[cfg(feature = "service")]
 	{
 		let args: Vec<String> = std::env::args().collect();
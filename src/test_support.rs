@@ -0,0 +1,211 @@
+//! Helpers for tests that stand in for desktop services: a lock around the
+//! environment they point at the stand-ins, a private D-Bus session bus, a
+//! private X server and a private Wayland compositor.
+
+use std::{
+	ffi::{OsStr, OsString},
+	fs,
+	io::{self, BufRead, BufReader},
+	path::PathBuf,
+	process::{Child, Command, Stdio},
+	sync::{Mutex, MutexGuard},
+	thread,
+	time::{Duration, Instant},
+};
+
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// Keeps other tests from reading or changing the environment until the guard
+/// is dropped.
+pub(crate) fn lock_env() -> MutexGuard<'static, ()> {
+	ENV_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Sets an environment variable. Callers hold [`lock_env`].
+pub(crate) fn set_env(key: &str, value: impl AsRef<OsStr>) {
+	// SAFETY: every test that touches the environment holds `ENV_LOCK`.
+	unsafe { std::env::set_var(key, value) }
+}
+
+/// Removes an environment variable. Callers hold [`lock_env`].
+pub(crate) fn remove_env(key: &str) {
+	// SAFETY: every test that touches the environment holds `ENV_LOCK`.
+	unsafe { std::env::remove_var(key) }
+}
+
+/// Environment variables set for one test, put back as they were when dropped.
+/// Callers hold [`lock_env`] for at least as long.
+#[derive(Default)]
+pub(crate) struct ScopedEnv {
+	saved: Vec<(&'static str, Option<OsString>)>,
+}
+
+impl ScopedEnv {
+	pub(crate) fn set(&mut self, key: &'static str, value: impl AsRef<OsStr>) {
+		self.saved.push((key, std::env::var_os(key)));
+		set_env(key, value);
+	}
+}
+
+impl Drop for ScopedEnv {
+	fn drop(&mut self) {
+		for (key, value) in self.saved.drain(..).rev() {
+			match value {
+				Some(value) => set_env(key, value),
+				None => remove_env(key),
+			}
+		}
+	}
+}
+
+/// A `dbus-daemon` of our own, torn down when dropped.
+pub(crate) struct PrivateBus {
+	daemon:      Child,
+	pub address: String,
+}
+
+impl PrivateBus {
+	/// Starts a session bus; `None` when `dbus-daemon` is not installed.
+	pub(crate) fn start() -> Option<Self> {
+		let mut daemon = match Command::new("dbus-daemon")
+			.args(["--session", "--nofork", "--print-address"])
+			.stdout(Stdio::piped())
+			.stderr(Stdio::null())
+			.spawn()
+		{
+			Ok(daemon) => daemon,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => {
+				eprintln!("skipping: dbus-daemon is not installed");
+				return None;
+			}
+			Err(e) => panic!("could not start dbus-daemon: {e}"),
+		};
+
+		let mut address = String::new();
+		BufReader::new(daemon.stdout.take()?).read_line(&mut address).expect("dbus-daemon address");
+		Some(Self { daemon, address: address.trim().to_string() })
+	}
+}
+
+impl Drop for PrivateBus {
+	fn drop(&mut self) {
+		let _ = self.daemon.kill();
+		let _ = self.daemon.wait();
+	}
+}
+
+/// An `Xvfb` display of our own, torn down when dropped.
+pub(crate) struct PrivateDisplay {
+	server:   Child,
+	/// The display name to put in `DISPLAY`, e.g. `:5`.
+	pub name: String,
+}
+
+impl PrivateDisplay {
+	/// Starts a virtual X server on a free display; `None` when `Xvfb` is not
+	/// installed.
+	pub(crate) fn start() -> Option<Self> {
+		let mut server = match Command::new("Xvfb")
+			.args(["-displayfd", "1", "-nolisten", "tcp"])
+			.stdout(Stdio::piped())
+			.stderr(Stdio::null())
+			.spawn()
+		{
+			Ok(server) => server,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => {
+				eprintln!("skipping: Xvfb is not installed");
+				return None;
+			}
+			Err(e) => panic!("could not start Xvfb: {e}"),
+		};
+
+		// Xvfb writes the display number once it accepts connections.
+		let mut number = String::new();
+		BufReader::new(server.stdout.take()?).read_line(&mut number).expect("Xvfb display number");
+		Some(Self { server, name: format!(":{}", number.trim()) })
+	}
+}
+
+impl Drop for PrivateDisplay {
+	fn drop(&mut self) {
+		let _ = self.server.kill();
+		let _ = self.server.wait();
+	}
+}
+
+/// A headless sway, or cage where sway is missing, torn down when dropped.
+pub(crate) struct PrivateCompositor {
+	server:      Child,
+	runtime_dir: PathBuf,
+	/// The socket path to put in `WAYLAND_DISPLAY`.
+	pub socket:  PathBuf,
+}
+
+impl PrivateCompositor {
+	/// Starts a compositor on wlroots' headless backend; `None` when neither
+	/// sway nor cage is installed.
+	pub(crate) fn start() -> Option<Self> {
+		let runtime_dir = std::env::temp_dir().join(format!("bob-compositor-{}", std::process::id()));
+		fs::create_dir_all(&runtime_dir).expect("runtime directory");
+		let config = runtime_dir.join("sway.conf");
+		fs::write(&config, "").expect("empty sway config");
+
+		let mut sway = Command::new("sway");
+		sway.arg("--config").arg(&config);
+		let mut cage = Command::new("cage");
+		cage.args(["--", "sleep", "infinity"]);
+
+		let server = [sway, cage].into_iter().find_map(|mut command| {
+			match command
+				.env("XDG_RUNTIME_DIR", &runtime_dir)
+				.env("WLR_BACKENDS", "headless")
+				.env("WLR_LIBINPUT_NO_DEVICES", "1")
+				.env_remove("WAYLAND_DISPLAY")
+				.env_remove("DISPLAY")
+				.stdout(Stdio::null())
+				.stderr(Stdio::null())
+				.spawn()
+			{
+				Ok(server) => Some(server),
+				Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+				Err(e) => panic!("could not start {:?}: {e}", command.get_program()),
+			}
+		});
+		let Some(mut server) = server else {
+			eprintln!("skipping: neither sway nor cage is installed");
+			let _ = fs::remove_dir_all(&runtime_dir);
+			return None;
+		};
+
+		// The compositor names its socket itself, and creates it once it
+		// accepts connections.
+		let deadline = Instant::now() + Duration::from_secs(10);
+		let socket = loop {
+			let socket = fs::read_dir(&runtime_dir)
+				.expect("runtime directory")
+				.flatten()
+				.map(|entry| entry.path())
+				.find(|path| {
+					path.extension().is_none()
+						&& path.file_name().is_some_and(|name| name.as_encoded_bytes().starts_with(b"wayland-"))
+				});
+			if let Some(socket) = socket {
+				break socket;
+			}
+			if let Some(status) = server.try_wait().expect("compositor status") {
+				panic!("the compositor exited before opening a socket: {status}");
+			}
+			assert!(Instant::now() < deadline, "the compositor did not open a socket");
+			thread::sleep(Duration::from_millis(20));
+		};
+		Some(Self { server, runtime_dir, socket })
+	}
+}
+
+impl Drop for PrivateCompositor {
+	fn drop(&mut self) {
+		let _ = self.server.kill();
+		let _ = self.server.wait();
+		let _ = fs::remove_dir_all(&self.runtime_dir);
+	}
+}